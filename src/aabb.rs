@@ -54,6 +54,11 @@ impl AABB {
         }
     }
 
+    pub fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        2.0 * d.dot(V3::new(d.y, d.z, d.x))
+    }
+
     pub fn longest_axis(&self) -> usize {
         let delta = self.max - self.min;
         delta.max_position()
@@ -77,15 +82,15 @@ impl AABB {
 
         if delta.x <= epsilon {
             self.min.x -= epsilon / 2.0;
-            self.min.x += epsilon / 2.0;
+            self.max.x += epsilon / 2.0;
         }
         if delta.y <= epsilon {
             self.min.y -= epsilon / 2.0;
-            self.min.y += epsilon / 2.0;
+            self.max.y += epsilon / 2.0;
         }
         if delta.z <= epsilon {
             self.min.z -= epsilon / 2.0;
-            self.min.z += epsilon / 2.0;
+            self.max.z += epsilon / 2.0;
         }
 
         self
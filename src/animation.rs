@@ -0,0 +1,316 @@
+use crate::camera::{Camera, CameraConfig, CameraError};
+use crate::hittable::Hittable;
+use crate::renderer::Renderer;
+use crate::screen::{Screen, ScreenError};
+use crate::types::P3;
+use crate::utility::derive_seed;
+use glam::DQuat;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+
+// the animated parameters of the camera at a point in time
+#[derive(Copy, Clone)]
+pub struct CameraKeyframe {
+    pub time: f64,
+    pub look_from: P3,
+    pub look_at: P3,
+    pub fov: f64,
+    pub focus_dist: f64,
+}
+
+impl CameraKeyframe {
+    pub const fn from_config(time: f64, config: &CameraConfig) -> Self {
+        Self {
+            time,
+            look_from: config.look_from,
+            look_at: config.look_at,
+            fov: config.fov,
+            focus_dist: config.focus_dist,
+        }
+    }
+
+    // look_from turns around look_at and moves in or out, so keyframes on a circle around the
+    // target orbit it instead of cutting across, two that are half a turn apart go either way
+    fn lerp(&self, other: &Self, s: f64) -> Self {
+        let look_at = self.look_at.lerp(other.look_at, s);
+        let (a, b) = (
+            self.look_from - self.look_at,
+            other.look_from - other.look_at,
+        );
+        let turn = DQuat::IDENTITY.slerp(DQuat::from_rotation_arc(a.normalize(), b.normalize()), s);
+        let distance = (b.length() - a.length()).mul_add(s, a.length());
+
+        Self {
+            time: (other.time - self.time).mul_add(s, self.time),
+            look_from: look_at + turn * a.normalize() * distance,
+            look_at,
+            fov: (other.fov - self.fov).mul_add(s, self.fov),
+            focus_dist: (other.focus_dist - self.focus_dist).mul_add(s, self.focus_dist),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AnimationError {
+    InvalidFrames(usize, usize),
+    InvalidFps(f64),
+    InvalidShutterFraction(f64),
+    Camera { frame: usize, source: CameraError },
+    Screen { path: PathBuf, source: ScreenError },
+}
+
+impl std::fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidFrames(first, last) => {
+                write!(
+                    f,
+                    "the first frame must not come after the last, got {first} to {last}"
+                )
+            }
+            Self::InvalidFps(fps) => write!(f, "fps must be positive, got {fps}"),
+            Self::InvalidShutterFraction(fraction) => {
+                write!(f, "the shutter fraction must be in [0, 1], got {fraction}")
+            }
+            Self::Camera { frame, source } => write!(f, "frame {frame}: camera: {source}"),
+            Self::Screen { path, source } => write!(f, "{}: {source}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Camera { source, .. } => Some(source),
+            Self::Screen { source, .. } => Some(source),
+            Self::InvalidFrames(..) | Self::InvalidFps(_) | Self::InvalidShutterFraction(_) => None,
+        }
+    }
+}
+
+// a sequence of frames, frame n shows the scene at n / fps seconds with the shutter open for
+// `shutter_fraction` of the frame interval, 0.5 being the usual 180 degree shutter, the scene's moving
+// objects use the same seconds as their time
+#[derive(Clone)]
+pub struct Animation {
+    pub frames: RangeInclusive<usize>,
+    pub fps: f64,
+    // the shutter times of the base camera are replaced by these
+    pub shutter_fraction: f64,
+    // between the keyframes the camera is interpolated, see CameraKeyframe::lerp, outside them
+    // it is held, without any it stays where the base camera is
+    pub camera_keyframes: Vec<CameraKeyframe>,
+}
+
+impl Animation {
+    pub const fn from(frames: RangeInclusive<usize>, fps: f64) -> Self {
+        Self {
+            frames,
+            fps,
+            shutter_fraction: 0.5,
+            camera_keyframes: Vec::new(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), AnimationError> {
+        if self.frames.is_empty() {
+            let (first, last) = self.frames.clone().into_inner();
+            return Err(AnimationError::InvalidFrames(first, last));
+        }
+
+        if !(self.fps > 0.0 && self.fps.is_finite()) {
+            return Err(AnimationError::InvalidFps(self.fps));
+        }
+
+        if !(0.0..=1.0).contains(&self.shutter_fraction) {
+            return Err(AnimationError::InvalidShutterFraction(
+                self.shutter_fraction,
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn frame_time(&self, frame: usize) -> f64 {
+        frame as f64 / self.fps
+    }
+
+    // the camera of one frame, every frame gets its own seed derived from the base seed so a
+    // frame renders the same whichever range it is rendered in
+    pub fn camera_at(&self, base: &CameraConfig, frame: usize) -> CameraConfig {
+        let time = self.frame_time(frame);
+
+        let mut keyframes = self.camera_keyframes.clone();
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let next = keyframes.partition_point(|x| x.time <= time);
+
+        let pose = if keyframes.is_empty() {
+            CameraKeyframe::from_config(time, base)
+        } else if next == 0 {
+            keyframes[0]
+        } else if next == keyframes.len() {
+            keyframes[next - 1]
+        } else {
+            let (a, b) = (&keyframes[next - 1], &keyframes[next]);
+            a.lerp(b, (time - a.time) / (b.time - a.time))
+        };
+
+        CameraConfig {
+            look_from: pose.look_from,
+            look_at: pose.look_at,
+            fov: pose.fov,
+            focus_dist: pose.focus_dist,
+            shutter_open: time,
+            shutter_close: self.shutter_fraction.mul_add(1.0 / self.fps, time),
+            seed: derive_seed(base.seed, &[frame as u64]),
+            ..*base
+        }
+    }
+
+    pub fn render_frame(
+        &self,
+        base: &CameraConfig,
+        frame: usize,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+    ) -> Result<Screen, AnimationError> {
+        self.validate()?;
+        let camera = Camera::from_config(&self.camera_at(base, frame))
+            .map_err(|source| AnimationError::Camera { frame, source })?;
+        Ok(camera.render(scene, renderer))
+    }
+
+    // renders every frame and writes it to the path from `pattern`, see frame_path, `on_frame`
+    // is called after each frame is written
+    pub fn render_sequence(
+        &self,
+        base: &CameraConfig,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+        pattern: &str,
+        mut on_frame: impl FnMut(usize, &Path),
+    ) -> Result<(), AnimationError> {
+        self.validate()?;
+        for frame in self.frames.clone() {
+            let screen = self.render_frame(base, frame, scene, renderer)?;
+            let path = frame_path(pattern, frame);
+            screen
+                .save(&path)
+                .map_err(|source| AnimationError::Screen {
+                    path: path.clone(),
+                    source,
+                })?;
+            on_frame(frame, &path);
+        }
+
+        Ok(())
+    }
+}
+
+// the last run of '#' in the pattern is replaced by the zero padded frame number, e.g.
+// "frame_####.png" gives "frame_0012.png", without one the number goes before the extension
+pub fn frame_path(pattern: &str, frame: usize) -> PathBuf {
+    if let Some(end) = pattern.rfind('#') {
+        let start = pattern[..end].trim_end_matches('#').len();
+        let width = end + 1 - start;
+        return PathBuf::from(format!(
+            "{}{frame:0width$}{}",
+            &pattern[..start],
+            &pattern[end + 1..]
+        ));
+    }
+
+    let path = Path::new(pattern);
+    let stem = path
+        .file_stem()
+        .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+    let mut name = format!("{stem}_{frame:04}");
+    if let Some(extension) = path.extension() {
+        name = format!("{name}.{}", extension.to_string_lossy());
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::animation::{Animation, CameraKeyframe, frame_path};
+    use crate::camera::CameraConfig;
+    use crate::lambertian::Lambertian;
+    use crate::renderer::FullRenderer;
+    use crate::sphere::Sphere;
+    use crate::transform::{AnimatedTransform, Keyframe};
+    use crate::types::{Color, P3, V3};
+    use std::ops::RangeInclusive;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    #[test]
+    fn check_frames_follow_the_keyframes() {
+        assert_eq!(
+            frame_path("out/f_###.png", 7),
+            PathBuf::from("out/f_007.png")
+        );
+        assert_eq!(frame_path("out/f.exr", 12), PathBuf::from("out/f_0012.exr"));
+
+        let base = CameraConfig {
+            image_width: 12,
+            image_height: 8,
+            samples_per_pixel: 4,
+            fov: 40.0,
+            ..CameraConfig::default()
+        };
+        let mut animation = Animation::from(0..=4, 2.0);
+        animation.camera_keyframes = vec![
+            CameraKeyframe {
+                look_from: P3::new(2.0, 0.0, -1.0),
+                fov: 60.0,
+                ..CameraKeyframe::from_config(1.0, &base)
+            },
+            CameraKeyframe::from_config(0.0, &base),
+        ];
+
+        // frame 1 is half a second in, half way round to the second keyframe and half way out
+        let camera = animation.camera_at(&base, 1);
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        let expected = P3::new(1.5 * half, 0.0, 1.5f64.mul_add(half, -1.0));
+        assert!((camera.look_from - expected).length() < 1e-9);
+        assert_eq!(camera.fov, 50.0);
+        assert_eq!((camera.shutter_open, camera.shutter_close), (0.5, 0.75));
+        assert_eq!(
+            animation.camera_at(&base, 4).look_from,
+            P3::new(2.0, 0.0, -1.0)
+        );
+        assert_ne!(camera.seed, animation.camera_at(&base, 2).seed);
+        assert!(
+            Animation::from(RangeInclusive::new(4, 2), 2.0)
+                .validate()
+                .is_err()
+        );
+
+        // a sphere moving across the view over the first second
+        let mat = Arc::new(Lambertian::from_color(Color::splat(0.5)));
+        let sphere = Arc::new(Sphere::static_sphere(P3::new(0.0, 0.0, -3.0), 0.5, mat));
+        let scene = AnimatedTransform::from(
+            sphere,
+            vec![
+                Keyframe::new(0.0),
+                Keyframe {
+                    translation: V3::new(-1.0, 0.0, 0.0),
+                    ..Keyframe::new(1.0)
+                },
+            ],
+        );
+        animation.camera_keyframes.clear();
+
+        let renderer = FullRenderer::new();
+        let render = |frame| {
+            animation
+                .render_frame(&base, frame, &scene, &renderer)
+                .unwrap()
+                .screen_data
+        };
+        assert_eq!(render(1), render(1));
+        assert_ne!(render(0), render(1));
+    }
+}
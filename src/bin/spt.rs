@@ -1,58 +1,40 @@
-use glam::DVec3;
-use spt::bvh::BVHNode;
-use spt::camera::initialize_camera;
+use spt::animation::Animation;
+use spt::bvh::{BVHBuildMethod, BVHLayout};
+use spt::camera::{Camera, CameraConfig, ShutterCurve};
+use spt::progress::CancelToken;
+use spt::progressive::{Accumulator, AdaptiveSampling, ProgressiveSettings};
 use spt::hittable_list::HittableList;
 use spt::lambertian::{Dielectric, DiffuseLight, Lambertian, Metal};
 use spt::quad::Quad;
 use spt::ray::Ray;
 use spt::sphere::Sphere;
 use spt::texture::{CheckerTexture, SolidColor};
+use spt::tiles::{Tile, TileOrder, TileSettings};
 use spt::types::{Color, P3, V3};
 use spt::utility::{make_prng_default, random_double, random_double_in_range};
 use std::sync::Arc;
-use std::time::Instant;
-use spt::renderer::{FullRenderer, NormalRenderer};
-
-fn quad_scene() {
-    fn camera_set_up() -> (
-        usize,
-        usize,
-        usize,
-        usize,
-        f64,
-        DVec3,
-        DVec3,
-        DVec3,
-        f64,
-        f64,
-        DVec3,
-    ) {
-        let image_width = 400;
-        let image_height = 400;
-        let samples_per_pixel = 1000;
-        let max_depth = 50;
-        let fov = 80.0f64;
-        let look_from = P3::new(0.0, 0.0, 9.0);
-        let look_at = P3::new(0.0, 0.0, 0.0);
-        let v_up = V3::new(0.0, 1.0, 0.0);
-
-        let defocus_angle = 0.0;
-        let focus_dist = 10.4;
-        let background = Color::new(0.8, 0.8, 0.8);
-
-        (
-            image_width,
-            image_height,
-            samples_per_pixel,
-            max_depth,
-            fov,
-            look_from,
-            look_at,
-            v_up,
-            defocus_angle,
-            focus_dist,
-            background,
-        )
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use spt::renderer::{Renderer, RendererKind};
+use spt::scene::Scene;
+use std::path::Path;
+
+fn quad_scene(bvh: BVHBuildMethod, layout: BVHLayout) -> Scene {
+    fn camera_set_up() -> CameraConfig {
+        CameraConfig {
+            image_width: 400,
+            image_height: 400,
+            samples_per_pixel: 1000,
+            max_depth: 50,
+            fov: 80.0f64,
+            look_from: P3::new(0.0, 0.0, 9.0),
+            look_at: P3::new(0.0, 0.0, 0.0),
+            v_up: V3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.4,
+            background: Color::new(0.8, 0.8, 0.8),
+            ..CameraConfig::default()
+        }
     }
 
     let mut world = HittableList::new();
@@ -94,55 +76,36 @@ fn quad_scene() {
         lower_teal,
     )));
 
-    let world_bvh = BVHNode::from(&mut world.objects);
-
-    let camera = initialize_camera(camera_set_up());
-
-    let renderer = FullRenderer{};
-    camera.render(&world_bvh, &renderer);
+    let primitive_count = world.objects.len();
+    let (world_bvh, bvh_stats) = layout.build(&mut world.objects, bvh);
+
+    Scene {
+        camera: camera_set_up(),
+        renderer: RendererKind::Full,
+        bvh_stats: Some(bvh_stats),
+        animation: None,
+        world: world_bvh,
+        lights: None,
+        primitive_count,
+    }
 }
 
-fn bouncing_balls() {
-    fn camera_set_up() -> (
-        usize,
-        usize,
-        usize,
-        usize,
-        f64,
-        DVec3,
-        DVec3,
-        DVec3,
-        f64,
-        f64,
-        DVec3,
-    ) {
-        let image_width = 1200;
-        let image_height = 500;
-        let samples_per_pixel = 150;
-        let max_depth = 15;
-        let fov = 20.0f64;
-
-        let look_from = P3::new(13.0, 2.0, 3.0);
-        let look_at = P3::new(0.0, 0.0, 0.0);
-        let v_up = V3::new(0.0, 1.0, 0.0);
-
-        let defocus_angle = 0.0;
-        let focus_dist = 10.0;
-        let background = Color::new(0.8, 0.8, 0.8);
-
-        (
-            image_width,
-            image_height,
-            samples_per_pixel,
-            max_depth,
-            fov,
-            look_from,
-            look_at,
-            v_up,
-            defocus_angle,
-            focus_dist,
-            background,
-        )
+fn bouncing_balls(bvh: BVHBuildMethod, layout: BVHLayout) -> Scene {
+    fn camera_set_up() -> CameraConfig {
+        CameraConfig {
+            image_width: 1200,
+            image_height: 500,
+            samples_per_pixel: 150,
+            max_depth: 15,
+            fov: 20.0f64,
+            look_from: P3::new(13.0, 2.0, 3.0),
+            look_at: P3::new(0.0, 0.0, 0.0),
+            v_up: V3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            background: Color::new(0.8, 0.8, 0.8),
+            ..CameraConfig::default()
+        }
     }
 
     let mut world = HittableList::new();
@@ -230,55 +193,36 @@ fn bouncing_balls() {
         1.0,
         mat_3,
     )));
-    let world_bvh = BVHNode::from(&mut world.objects);
-
-    let camera = initialize_camera(camera_set_up());
-
-    let renderer = FullRenderer{};
-    camera.render(&world_bvh, &renderer);
+    let primitive_count = world.objects.len();
+    let (world_bvh, bvh_stats) = layout.build(&mut world.objects, bvh);
+
+    Scene {
+        camera: camera_set_up(),
+        renderer: RendererKind::Full,
+        bvh_stats: Some(bvh_stats),
+        animation: None,
+        world: world_bvh,
+        lights: None,
+        primitive_count,
+    }
 }
 
-fn still_balls() {
-    fn camera_set_up() -> (
-        usize,
-        usize,
-        usize,
-        usize,
-        f64,
-        DVec3,
-        DVec3,
-        DVec3,
-        f64,
-        f64,
-        DVec3,
-    ) {
-        let image_width = 2560;
-        let image_height = 1440;
-        let samples_per_pixel = 1;
-        let max_depth = 100;
-        let fov = 20.0f64;
-
-        let look_from = P3::new(13.0, 2.0, 3.0);
-        let look_at = P3::new(0.0, 0.0, 0.0);
-        let v_up = V3::new(0.0, 1.0, 0.0);
-
-        let defocus_angle = 0.0;
-        let focus_dist = 10.0;
-        let background = Color::new(0.8, 0.8, 0.8);
-
-        (
-            image_width,
-            image_height,
-            samples_per_pixel,
-            max_depth,
-            fov,
-            look_from,
-            look_at,
-            v_up,
-            defocus_angle,
-            focus_dist,
-            background,
-        )
+fn still_balls(bvh: BVHBuildMethod, layout: BVHLayout) -> Scene {
+    fn camera_set_up() -> CameraConfig {
+        CameraConfig {
+            image_width: 2560,
+            image_height: 1440,
+            samples_per_pixel: 1,
+            max_depth: 100,
+            fov: 20.0f64,
+            look_from: P3::new(13.0, 2.0, 3.0),
+            look_at: P3::new(0.0, 0.0, 0.0),
+            v_up: V3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            background: Color::new(0.8, 0.8, 0.8),
+            ..CameraConfig::default()
+        }
     }
 
     let mut world = HittableList::new();
@@ -362,7 +306,8 @@ fn still_balls() {
 
     world.add(light_sphere);
 
-    let world_bvh = BVHNode::from(&mut world.objects);
+    let primitive_count = world.objects.len() + 1;
+    let (world_bvh, bvh_stats) = layout.build(&mut world.objects, bvh);
 
     let mut world_2 = HittableList::new();
 
@@ -374,53 +319,33 @@ fn still_balls() {
 
     world_2.add(Box::new(world_bvh));
 
-    let camera = initialize_camera(camera_set_up());
-
-    let renderer = NormalRenderer{};
-    camera.render(&world_2, &renderer);
+    Scene {
+        camera: camera_set_up(),
+        renderer: RendererKind::Normal,
+        world: Arc::new(world_2),
+        lights: None,
+        primitive_count,
+        bvh_stats: Some(bvh_stats),
+        animation: None,
+    }
 }
 
-fn simple_light() {
-    fn camera_set_up() -> (
-        usize,
-        usize,
-        usize,
-        usize,
-        f64,
-        DVec3,
-        DVec3,
-        DVec3,
-        f64,
-        f64,
-        DVec3,
-    ) {
-        let image_width = 2560;
-        let image_height = 1440;
-        let samples_per_pixel = 1;
-        let max_depth = 50;
-        let fov = 20.0f64;
-
-        let look_from = P3::new(26.0, 3.0, 6.0);
-        let look_at = P3::new(0.0, 2.0, 0.0);
-        let v_up = V3::new(0.0, 1.0, 0.0);
-
-        let defocus_angle = 0.0;
-        let focus_dist = 10.0;
-        let background = Color::new(0.0, 0.0, 0.0);
-
-        (
-            image_width,
-            image_height,
-            samples_per_pixel,
-            max_depth,
-            fov,
-            look_from,
-            look_at,
-            v_up,
-            defocus_angle,
-            focus_dist,
-            background,
-        )
+fn simple_light(_bvh: BVHBuildMethod, _layout: BVHLayout) -> Scene {
+    fn camera_set_up() -> CameraConfig {
+        CameraConfig {
+            image_width: 2560,
+            image_height: 1440,
+            samples_per_pixel: 1,
+            max_depth: 50,
+            fov: 20.0f64,
+            look_from: P3::new(26.0, 3.0, 6.0),
+            look_at: P3::new(0.0, 2.0, 0.0),
+            v_up: V3::new(0.0, 1.0, 0.0),
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            background: Color::new(0.0, 0.0, 0.0),
+            ..CameraConfig::default()
+        }
     }
 
     let mut world = HittableList::new();
@@ -448,26 +373,567 @@ fn simple_light() {
     let diff_mat = Arc::new(DiffuseLight::from_color(Color::new(4.0, 4.0, 4.0)));
 
     world.add(Box::new(Quad::new(
+        P3::new(3.0, 1.0, -2.0),
+        2.0 * V3::X,
+        2.0 * V3::Y,
+        diff_mat.clone(),
+    )));
+
+    let mut lights = HittableList::new();
+
+    lights.add(Box::new(Quad::new(
         P3::new(3.0, 1.0, -2.0),
         2.0 * V3::X,
         2.0 * V3::Y,
         diff_mat,
     )));
 
-    let camera = initialize_camera(camera_set_up());
-    let renderer = NormalRenderer{};
-    camera.render(&world, &renderer);
+    Scene {
+        camera: camera_set_up(),
+        renderer: RendererKind::Full,
+        primitive_count: world.objects.len(),
+        world: Arc::new(world),
+        lights: Some(Arc::new(lights)),
+        bvh_stats: None,
+        animation: None,
+    }
 }
 
-fn main() {
-    let now = Instant::now();
+const DEFAULT_PASS_SAMPLES: usize = 16;
+
+type SceneBuilder = fn(BVHBuildMethod, BVHLayout) -> Scene;
+
+const BUILT_IN_SCENES: [(&str, SceneBuilder); 4] = [
+    ("quad_scene", quad_scene),
+    ("bouncing_balls", bouncing_balls),
+    ("still_balls", still_balls),
+    ("simple_light", simple_light),
+];
+
+const USAGE: &str = "usage: spt [options]
+
+options:
+    --scene <name|file>    built-in scene or scene file to render (default still_balls)
+    --list                 list the built-in scenes
+    --width <n>            image width in pixels
+    --height <n>           image height in pixels
+    --spp <n>              samples per pixel
+    --max-depth <n>        maximum number of bounces
+    --seed <n>             seed of the render
+    --renderer <name>      normal, full, mis, mis-balance or mis-power
+    --bvh <method>         bvh build method, median or sah
+    --bvh-layout <layout>  bvh memory layout, tree or linear
+    --output <path>        output image, the format follows the extension (default output.png)
+    --pass-spp <n>         render progressively in passes of n spp (default 16), writing the
+                           output after each
+    --time-limit <s>       stop the render after this many seconds, a progressive render keeps
+                           the passes done so far, a tiled one the tiles finished so far
+    --snapshot-every <s>   write the output at most every this many seconds instead of every pass
+    --checkpoint <path>    render progressively, saving the progress with every snapshot and
+                           resuming from the file if it exists
+    --adaptive <t>         render progressively and stop sampling pixels whose relative error
+                           is below t, e.g. 0.01
+    --min-spp <n>          samples every pixel takes before it can stop (default 16)
+    --sample-map <path>    write the number of samples of every pixel as an image
+    --tile-size <n>        edge length of the tiles in pixels (default 16)
+    --tile-order <order>   order the tiles are rendered in, hilbert, spiral or scanline
+    --crop <x0,y0,x1,y1>   only render the pixels in this window, the rest stays black
+    --shutter <open,close> times the shutter opens and closes (default 0,1)
+    --shutter-curve <name> box, triangle or rolling
+    --no-motion-blur       trace every ray at the time the shutter opens
+    --frames <first,last>  render these frames of an animation, the output gets the frame number
+                           in place of a run of '#' or before the extension
+    --fps <n>              frames per second of the animation (default 24)
+    --help                 show this message";
+
+struct Options {
+    scene: String,
+    width: Option<usize>,
+    height: Option<usize>,
+    samples_per_pixel: Option<usize>,
+    max_depth: Option<usize>,
+    seed: Option<u64>,
+    renderer: Option<RendererKind>,
+    bvh: Option<BVHBuildMethod>,
+    bvh_layout: Option<BVHLayout>,
+    output: String,
+    pass_samples: Option<usize>,
+    time_limit: Option<Duration>,
+    snapshot_interval: Option<Duration>,
+    checkpoint: Option<String>,
+    adaptive_threshold: Option<f64>,
+    min_samples: Option<u64>,
+    sample_map: Option<String>,
+    tile_size: Option<usize>,
+    tile_order: Option<TileOrder>,
+    crop: Option<Tile>,
+    shutter: Option<(f64, f64)>,
+    shutter_curve: Option<ShutterCurve>,
+    motion_blur: Option<bool>,
+    frames: Option<(usize, usize)>,
+    fps: Option<f64>,
+}
+
+enum Command {
+    Render(Box<Options>),
+    List,
+    Help,
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+        value
+            .parse()
+            .map_err(|_| format!("{flag} expects a whole number, got `{value}`"))
+    }
+
+    fn seconds(flag: &str, value: &str) -> Result<Duration, String> {
+        value
+            .parse()
+            .ok()
+            .and_then(|x| Duration::try_from_secs_f64(x).ok())
+            .ok_or_else(|| format!("{flag} expects a number of seconds, got `{value}`"))
+    }
+
+    fn window(flag: &str, value: &str) -> Result<Tile, String> {
+        let bounds: Vec<usize> = value
+            .split(',')
+            .map(|x| x.trim().parse())
+            .collect::<Result<_, _>>()
+            .map_err(|_| format!("{flag} expects x0,y0,x1,y1, got `{value}`"))?;
+
+        match bounds[..] {
+            [x0, y0, x1, y1] if x0 < x1 && y0 < y1 => Ok(Tile::new(x0, y0, x1, y1)),
+            _ => Err(format!("{flag} expects x0,y0,x1,y1 with x0 < x1 and y0 < y1, got `{value}`")),
+        }
+    }
+
+    let mut options = Options {
+        scene: "still_balls".to_string(),
+        width: None,
+        height: None,
+        samples_per_pixel: None,
+        max_depth: None,
+        seed: None,
+        renderer: None,
+        bvh: None,
+        bvh_layout: None,
+        output: "output.png".to_string(),
+        pass_samples: None,
+        time_limit: None,
+        snapshot_interval: None,
+        checkpoint: None,
+        adaptive_threshold: None,
+        min_samples: None,
+        sample_map: None,
+        tile_size: None,
+        tile_order: None,
+        crop: None,
+        shutter: None,
+        shutter_curve: None,
+        motion_blur: None,
+        frames: None,
+        fps: None,
+    };
+
+    let mut args = args;
+
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--help" | "-h" => return Ok(Command::Help),
+            "--list" => return Ok(Command::List),
+            "--no-motion-blur" => {
+                options.motion_blur = Some(false);
+                continue;
+            }
+            _ => {}
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{flag} expects a value"))?;
+
+        match flag.as_str() {
+            "--scene" => options.scene = value,
+            "--width" => options.width = Some(number(&flag, &value)?),
+            "--height" => options.height = Some(number(&flag, &value)?),
+            "--spp" => options.samples_per_pixel = Some(number(&flag, &value)?),
+            "--max-depth" => options.max_depth = Some(number(&flag, &value)?),
+            "--seed" => options.seed = Some(number(&flag, &value)?),
+            "--renderer" => {
+                options.renderer = Some(
+                    RendererKind::from_name(&value)
+                        .ok_or_else(|| format!("unknown renderer `{value}`"))?,
+                );
+            }
+            "--bvh" => {
+                options.bvh = Some(
+                    BVHBuildMethod::from_name(&value)
+                        .ok_or_else(|| format!("unknown bvh build method `{value}`"))?,
+                );
+            }
+            "--bvh-layout" => {
+                options.bvh_layout = Some(
+                    BVHLayout::from_name(&value)
+                        .ok_or_else(|| format!("unknown bvh layout `{value}`"))?,
+                );
+            }
+            "--output" => options.output = value,
+            "--pass-spp" => options.pass_samples = Some(number(&flag, &value)?),
+            "--time-limit" => options.time_limit = Some(seconds(&flag, &value)?),
+            "--snapshot-every" => options.snapshot_interval = Some(seconds(&flag, &value)?),
+            "--checkpoint" => options.checkpoint = Some(value),
+            "--adaptive" => {
+                options.adaptive_threshold = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|x: &f64| *x > 0.0)
+                        .ok_or_else(|| format!("{flag} expects a positive number, got `{value}`"))?,
+                );
+            }
+            "--min-spp" => options.min_samples = Some(number(&flag, &value)?),
+            "--sample-map" => options.sample_map = Some(value),
+            "--tile-size" => options.tile_size = Some(number(&flag, &value)?),
+            "--tile-order" => {
+                options.tile_order = Some(
+                    TileOrder::from_name(&value)
+                        .ok_or_else(|| format!("unknown tile order `{value}`"))?,
+                );
+            }
+            "--crop" => options.crop = Some(window(&flag, &value)?),
+            "--shutter" => {
+                let times = value
+                    .split_once(',')
+                    .and_then(|(a, b)| Some((a.trim().parse().ok()?, b.trim().parse().ok()?)));
+                options.shutter =
+                    Some(times.ok_or_else(|| format!("{flag} expects open,close, got `{value}`"))?);
+            }
+            "--frames" => {
+                let frames = value
+                    .split_once(',')
+                    .and_then(|(a, b)| Some((a.trim().parse().ok()?, b.trim().parse().ok()?)))
+                    .filter(|(first, last)| first <= last);
+                options.frames = Some(
+                    frames.ok_or_else(|| format!("{flag} expects first,last, got `{value}`"))?,
+                );
+            }
+            "--fps" => {
+                options.fps = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|x: &f64| *x > 0.0)
+                        .ok_or_else(|| format!("{flag} expects a positive number, got `{value}`"))?,
+                );
+            }
+            "--shutter-curve" => {
+                options.shutter_curve = Some(
+                    ShutterCurve::from_name(&value)
+                        .ok_or_else(|| format!("unknown shutter curve `{value}`"))?,
+                );
+            }
+            _ => return Err(format!("unknown option `{flag}`")),
+        }
+    }
 
-    still_balls();
+    Ok(Command::Render(Box::new(options)))
+}
 
-    let stop = Instant::now();
+fn load_scene(
+    name: &str,
+    bvh: Option<BVHBuildMethod>,
+    layout: Option<BVHLayout>,
+) -> Result<Scene, String> {
+    if let Some((_, build)) = BUILT_IN_SCENES.iter().find(|x| x.0 == name) {
+        return Ok(build(
+            bvh.unwrap_or(BVHBuildMethod::Median),
+            layout.unwrap_or(BVHLayout::Tree),
+        ));
+    }
 
-    let delta = (stop - now).as_secs_f64();
+    if Path::new(name).is_file() {
+        return Scene::load_with(name, bvh, layout).map_err(|e| e.to_string());
+    }
 
-    println!("{} secs", delta);
-    println!("Done Running!");
+    Err(format!(
+        "`{name}` is neither a built-in scene nor a scene file, see --list"
+    ))
+}
+
+// resumes from the checkpoint if there is one, and writes the output and the checkpoint with
+// every snapshot
+fn render_progressive(
+    options: &Options,
+    scene: &Scene,
+    camera: &Camera,
+    renderer: &dyn Renderer,
+) -> Result<Accumulator, String> {
+    let settings = ProgressiveSettings {
+        time_limit: options.time_limit,
+        snapshot_interval: options.snapshot_interval,
+        adaptive: options.adaptive_threshold.map(|x| AdaptiveSampling {
+            min_samples: options.min_samples.unwrap_or(16),
+            ..AdaptiveSampling::new(x)
+        }),
+        ..ProgressiveSettings::new(options.pass_samples.unwrap_or(DEFAULT_PASS_SAMPLES))
+    };
+
+    let save = |x: &Accumulator| {
+        if let Err(e) = x.to_screen().save(&options.output) {
+            eprintln!("could not write snapshot: {e}");
+        }
+        if let Some(path) = &options.checkpoint
+            && let Err(e) = x.save_checkpoint(path)
+        {
+            eprintln!("could not write checkpoint: {e}");
+        }
+    };
+
+    let world = scene.world.as_ref();
+
+    let accumulator = match &options.checkpoint {
+        Some(path) if Path::new(path).is_file() => {
+            let accumulator = Accumulator::load_checkpoint(path).map_err(|e| e.to_string())?;
+            println!("resuming     {} spp from {path}", accumulator.samples());
+            camera
+                .resume_progressive(world, renderer, &settings, accumulator, save)
+                .map_err(|e| format!("{path}: {e}"))?
+        }
+        _ => camera.render_progressive(world, renderer, &settings, save),
+    };
+
+    if let Some(path) = &options.checkpoint {
+        accumulator.save_checkpoint(path).map_err(|e| e.to_string())?;
+    }
+
+    if let Some(path) = &options.sample_map {
+        accumulator
+            .sample_count_screen()
+            .save(path)
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(accumulator)
+}
+
+// writes one numbered image per frame
+fn render_animation(
+    options: &Options,
+    scene: &Scene,
+    animation: &Animation,
+    renderer: &dyn Renderer,
+) -> Result<(), String> {
+    let progressive = options.pass_samples.is_some()
+        || options.time_limit.is_some()
+        || options.checkpoint.is_some()
+        || options.adaptive_threshold.is_some();
+
+    if progressive || options.crop.is_some() {
+        return Err("animations are rendered in one pass without a crop window".to_string());
+    }
+    if options.shutter.is_some() {
+        return Err(
+            "--shutter does not apply to animations, every frame opens the shutter itself"
+                .to_string(),
+        );
+    }
+
+    let render_start = Instant::now();
+    let mut frame_start = Instant::now();
+
+    animation
+        .render_sequence(
+            &scene.camera,
+            scene.world.as_ref(),
+            renderer,
+            &options.output,
+            |frame, path| {
+                println!(
+                    "frame {frame:<6} {:.3} s  {}",
+                    frame_start.elapsed().as_secs_f64(),
+                    path.display()
+                );
+                frame_start = Instant::now();
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    println!("scene        {}", options.scene);
+    println!(
+        "frames       {} to {} at {} fps",
+        animation.frames.start(),
+        animation.frames.end(),
+        animation.fps
+    );
+    println!(
+        "render time  {:.3} s",
+        render_start.elapsed().as_secs_f64()
+    );
+
+    Ok(())
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let build_start = Instant::now();
+    let mut scene = load_scene(&options.scene, options.bvh, options.bvh_layout)?;
+    let build_time = build_start.elapsed().as_secs_f64();
+
+    let config = &mut scene.camera;
+    config.image_width = options.width.unwrap_or(config.image_width);
+    config.image_height = options.height.unwrap_or(config.image_height);
+    config.samples_per_pixel = options.samples_per_pixel.unwrap_or(config.samples_per_pixel);
+    config.max_depth = options.max_depth.unwrap_or(config.max_depth);
+    config.seed = options.seed.unwrap_or(config.seed);
+    if let Some((open, close)) = options.shutter {
+        config.shutter_open = open;
+        config.shutter_close = close;
+    }
+    config.shutter_curve = options.shutter_curve.unwrap_or(config.shutter_curve);
+    config.motion_blur = options.motion_blur.unwrap_or(config.motion_blur);
+    scene.renderer = options.renderer.unwrap_or(scene.renderer);
+
+    let camera = scene.make_camera().map_err(|e| format!("camera: {e}"))?;
+    let renderer = scene.make_renderer();
+
+    let mut animation = scene.animation.clone();
+    if let Some((first, last)) = options.frames {
+        let base = animation.unwrap_or_else(|| Animation::from(0..=0, 24.0));
+        animation = Some(Animation {
+            frames: first..=last,
+            ..base
+        });
+    }
+
+    if let Some(animation) = &mut animation {
+        animation.fps = options.fps.unwrap_or(animation.fps);
+        return render_animation(options, &scene, animation, renderer.as_ref());
+    } else if options.fps.is_some() {
+        return Err("--fps needs --frames or a scene with an animation".to_string());
+    }
+
+    let render_start = Instant::now();
+
+    // the time limit works for both, progressive renders check it between passes
+    let progressive = options.pass_samples.is_some()
+        || options.checkpoint.is_some()
+        || options.adaptive_threshold.is_some();
+
+    if progressive && options.crop.is_some() {
+        return Err("--crop can not be combined with a progressive render".to_string());
+    }
+
+    let tile_settings = TileSettings {
+        tile_size: options.tile_size.unwrap_or(16),
+        order: options.tile_order.unwrap_or(TileOrder::Hilbert),
+        crop: options.crop,
+    };
+
+    let mut pixels = camera.image_width() * camera.image_height();
+
+    let (screen, samples_per_pixel, samples) = if progressive {
+        let accumulator = render_progressive(options, &scene, &camera, renderer.as_ref())?;
+        let samples = accumulator.total_samples() as f64;
+        (accumulator.to_screen(), accumulator.samples(), samples)
+    } else {
+        let tiles = tile_settings.make_tiles(camera.image_width(), camera.image_height());
+        pixels = tiles.iter().map(Tile::pixel_count).sum();
+
+        // a watchdog stops the workers once the time is up, the thread is left behind if the
+        // render finishes first
+        let cancel = CancelToken::new();
+        if let Some(limit) = options.time_limit {
+            let cancel = cancel.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(limit);
+                cancel.cancel();
+            });
+        }
+
+        let pixels_done = AtomicUsize::new(0);
+        let screen = camera.render_tiles(
+            scene.world.as_ref(),
+            renderer.as_ref(),
+            &tile_settings,
+            &cancel,
+            |x| {
+                pixels_done.fetch_max(x.pixels_done, Ordering::Relaxed);
+                let remaining = x.remaining().map_or(0.0, |x| x.as_secs_f64());
+                eprint!(
+                    "\rprogress     {:5.1}%, {}/{} tiles, {remaining:.0} s left   ",
+                    100.0 * x.fraction(),
+                    x.tiles_done,
+                    x.tile_count
+                );
+            },
+        );
+        eprintln!();
+
+        if cancel.is_cancelled() {
+            println!("stopped      after the time limit, the unfinished tiles are black");
+        }
+
+        let samples = (pixels_done.into_inner() * camera.samples_per_pixel()) as f64;
+        (screen, camera.samples_per_pixel(), samples)
+    };
+
+    let render_time = render_start.elapsed().as_secs_f64();
+
+    screen.save(&options.output).map_err(|e| e.to_string())?;
+
+    let bvh = scene.bvh_stats.map_or_else(
+        || "none".to_string(),
+        |x| {
+            format!(
+                "{} nodes, depth {}, sah cost {:.2}",
+                x.node_count, x.depth, x.sah_cost
+            )
+        },
+    );
+
+    println!("scene        {}", options.scene);
+    println!(
+        "resolution   {}x{}",
+        camera.image_width(),
+        camera.image_height()
+    );
+    println!(
+        "samples      {samples_per_pixel} spp ({:.1} on average), max depth {}",
+        samples / pixels as f64,
+        scene.camera.max_depth
+    );
+    println!("renderer     {}", scene.renderer.name());
+    println!("primitives   {}", scene.primitive_count);
+    println!("bvh          {bvh}");
+    println!("build time   {build_time:.3} s");
+    println!("render time  {render_time:.3} s");
+    println!("samples/sec  {:.0}", samples / render_time);
+    println!("output       {}", options.output);
+
+    Ok(())
+}
+
+fn main() {
+    let command = match parse_args(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    match command {
+        Command::Help => println!("{USAGE}"),
+        Command::List => {
+            for (name, _) in BUILT_IN_SCENES {
+                println!("{name}");
+            }
+        }
+        Command::Render(options) => {
+            if let Err(e) = run(&options) {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
 }
@@ -2,72 +2,340 @@ use crate::aabb::AABB;
 use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
 use crate::interval::Interval;
+use crate::linear_bvh::LinearBVH;
 use crate::ray::Ray;
+use crate::types::V3;
+use rayon::prelude::*;
 use smolprng::{JsfLarge, PRNG};
 use std::cmp::Ordering;
 use std::sync::Arc;
 
+// relative costs used to estimate the expected cost of a tree with the surface area heuristic
+pub(crate) const TRAVERSAL_COST: f64 = 0.125;
+pub(crate) const INTERSECTION_COST: f64 = 1.0;
+const SAH_BINS: usize = 16;
+
+// slices smaller than this are always built on the current thread
+pub(crate) const PARALLEL_BUILD_THRESHOLD: usize = 4096;
+
+#[derive(Copy, Clone)]
+pub enum BVHBuildMethod {
+    Median,
+    SAH,
+}
+
+impl BVHBuildMethod {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "median" => Some(Self::Median),
+            "sah" => Some(Self::SAH),
+            _ => None,
+        }
+    }
+}
+
+// how the built tree is stored, a tree of BVHNodes or a single LinearBVH array
+#[derive(Copy, Clone)]
+pub enum BVHLayout {
+    Tree,
+    Linear,
+}
+
+impl BVHLayout {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tree" => Some(Self::Tree),
+            "linear" => Some(Self::Linear),
+            _ => None,
+        }
+    }
+
+    // takes the objects out of the list
+    pub fn build(
+        &self,
+        obj_list: &mut Vec<Box<dyn Hittable>>,
+        method: BVHBuildMethod,
+    ) -> (Arc<dyn Hittable>, BVHStats) {
+        match self {
+            Self::Tree => {
+                let bvh = BVHNode::par_from_with(obj_list, method);
+                let stats = bvh.stats();
+                (Arc::new(bvh), stats)
+            }
+            Self::Linear => {
+                let bvh = LinearBVH::par_from_with(obj_list, method);
+                let stats = bvh.stats();
+                (Arc::new(bvh), stats)
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct BVHStats {
+    pub node_count: usize,
+    pub depth: usize,
+    pub sah_cost: f64,
+}
+
+impl BVHStats {
+    fn from_children(
+        aabb: &AABB,
+        left: (&AABB, Option<Self>),
+        right: (&AABB, Option<Self>),
+    ) -> Self {
+        let area = aabb.surface_area().max(f64::MIN_POSITIVE);
+
+        // children that are not nodes are primitives
+        let cost = |(child_aabb, stats): (&AABB, Option<Self>)| {
+            let child_cost = stats.map_or(INTERSECTION_COST, |x| x.sah_cost);
+            child_aabb.surface_area() / area * child_cost
+        };
+
+        let node_count = |x: Option<Self>| x.map_or(0, |x| x.node_count);
+        let depth = |x: Option<Self>| x.map_or(0, |x| x.depth);
+
+        Self {
+            node_count: 1 + node_count(left.1) + node_count(right.1),
+            depth: 1 + depth(left.1).max(depth(right.1)),
+            sah_cost: TRAVERSAL_COST + cost(left) + cost(right),
+        }
+    }
+}
+
 pub struct BVHNode {
     left: Arc<dyn Hittable>,
     right: Arc<dyn Hittable>,
     bounding_box: AABB,
+    stats: BVHStats,
 }
 
 impl BVHNode {
     pub fn from_two_hittables(obj1: Arc<dyn Hittable>, obj2: Arc<dyn Hittable>) -> Self {
         let aabb = AABB::from_aabbs(&obj1.bounding_box(), &obj2.bounding_box());
+        let stats = BVHStats::from_children(
+            &aabb,
+            (&obj1.bounding_box(), None),
+            (&obj2.bounding_box(), None),
+        );
         Self {
             left: obj1,
             right: obj2,
             bounding_box: aabb,
+            stats,
         }
     }
 
     pub fn from(obj_list: &mut Vec<Box<dyn Hittable>>) -> Self {
-        Self::make_level(obj_list, 0, obj_list.len())
+        Self::from_with(obj_list, BVHBuildMethod::Median)
+    }
+
+    // takes the objects out of the list
+    pub fn from_with(obj_list: &mut Vec<Box<dyn Hittable>>, method: BVHBuildMethod) -> Self {
+        let mut objects: Vec<Arc<dyn Hittable>> = obj_list.drain(..).map(Arc::from).collect();
+        Self::make_level(&mut objects, method, false)
+    }
+
+    // builds the two halves of large slices on the rayon pool, the tree is the same as the one
+    // from_with builds
+    pub fn par_from_with(obj_list: &mut Vec<Box<dyn Hittable>>, method: BVHBuildMethod) -> Self {
+        let mut objects: Vec<Arc<dyn Hittable>> = obj_list.drain(..).map(Arc::from).collect();
+        Self::make_level(&mut objects, method, true)
     }
 
-    pub fn make_level(obj_list: &mut Vec<Box<dyn Hittable>>, start: usize, end: usize) -> Self {
-        let mut aabb = AABB::new();
+    pub fn make_level(
+        objects: &mut [Arc<dyn Hittable>],
+        method: BVHBuildMethod,
+        parallel: bool,
+    ) -> Self {
+        let parallel = parallel && objects.len() >= PARALLEL_BUILD_THRESHOLD;
 
-        for obj in obj_list.iter() {
+        let mut aabb = objects[0].bounding_box();
+
+        for obj in objects.iter() {
             aabb = AABB::from_aabbs(&aabb, &obj.bounding_box());
         }
 
         let axis = aabb.longest_axis();
 
-        let object_span = end - start;
-
-        let (left, right) = match object_span {
+        let ((left, left_stats), (right, right_stats)) = match objects.len() {
             1 => {
-                let first: Arc<dyn Hittable> = obj_list.remove(0).into();
-                let second: Arc<dyn Hittable> = first.clone();
-                (first, second)
+                let first = objects[0].clone();
+                let second = first.clone();
+                ((first, None), (second, None))
             }
             2 => {
-                let first: Arc<dyn Hittable> = obj_list.remove(0).into();
-                let second: Arc<dyn Hittable> = obj_list.remove(0).into();
+                let first = objects[0].clone();
+                let second = objects[1].clone();
 
                 match Self::hittable_compare(&*first, &*second, axis) {
-                    Ordering::Less => (first, second),
-                    _ => (second, first),
+                    Ordering::Less => ((first, None), (second, None)),
+                    _ => ((second, None), (first, None)),
                 }
             }
             _ => {
-                obj_list.sort_by(|x, y| Self::hittable_compare(&**x, &**y, axis));
-                let mid = start + object_span / 2;
+                let mid = Self::partition(objects, method, axis, parallel);
+                let (left_objects, right_objects) = objects.split_at_mut(mid);
 
-                let left: Arc<dyn Hittable> = Arc::new(Self::make_level(obj_list, start, mid));
-                let right: Arc<dyn Hittable> = Arc::new(Self::make_level(obj_list, mid, end));
-                (left, right)
+                if parallel {
+                    rayon::join(
+                        || Self::make_child(left_objects, method, parallel),
+                        || Self::make_child(right_objects, method, parallel),
+                    )
+                } else {
+                    (
+                        Self::make_child(left_objects, method, parallel),
+                        Self::make_child(right_objects, method, parallel),
+                    )
+                }
             }
         };
 
+        let stats = BVHStats::from_children(
+            &aabb,
+            (&left.bounding_box(), left_stats),
+            (&right.bounding_box(), right_stats),
+        );
+
         Self {
             left,
             right,
             bounding_box: aabb,
+            stats,
+        }
+    }
+
+    // a single object is used directly instead of being wrapped in a node
+    fn make_child(
+        objects: &mut [Arc<dyn Hittable>],
+        method: BVHBuildMethod,
+        parallel: bool,
+    ) -> (Arc<dyn Hittable>, Option<BVHStats>) {
+        if objects.len() == 1 {
+            return (objects[0].clone(), None);
+        }
+        let node = Self::make_level(objects, method, parallel);
+        let stats = node.stats;
+        (Arc::new(node), Some(stats))
+    }
+
+    // reorders the objects and returns how many of them go into the first child
+    pub(crate) fn partition(
+        objects: &mut [Arc<dyn Hittable>],
+        method: BVHBuildMethod,
+        axis: usize,
+        parallel: bool,
+    ) -> usize {
+        match method {
+            BVHBuildMethod::Median => None,
+            BVHBuildMethod::SAH => Self::sah_partition(objects, parallel),
         }
+        .unwrap_or_else(|| {
+            let mid = objects.len() / 2;
+            objects.select_nth_unstable_by(mid, |x, y| Self::hittable_compare(&**x, &**y, axis));
+            mid
+        })
+    }
+
+    // bins the centroids along every axis and picks the cheapest split by the surface area
+    // heuristic, the objects are partitioned so the first returned count go into the left child,
+    // returns None when no split separates the centroids
+    fn sah_partition(objects: &mut [Arc<dyn Hittable>], parallel: bool) -> Option<usize> {
+        let centroid = |x: &Arc<dyn Hittable>| {
+            let aabb = x.bounding_box();
+            0.5 * (aabb.min + aabb.max)
+        };
+
+        let centroids: Vec<V3> = if parallel {
+            objects.par_iter().map(centroid).collect()
+        } else {
+            objects.iter().map(centroid).collect()
+        };
+
+        let centroid_min = centroids.iter().fold(V3::splat(f64::MAX), |a, b| a.min(*b));
+        let centroid_max = centroids.iter().fold(V3::splat(f64::MIN), |a, b| a.max(*b));
+        let extent = centroid_max - centroid_min;
+
+        let bin_of = |c: V3, axis: usize| {
+            let x = (c[axis] - centroid_min[axis]) / extent[axis];
+            ((x * SAH_BINS as f64) as usize).min(SAH_BINS - 1)
+        };
+
+        let mut best: Option<(f64, usize, usize)> = None;
+
+        for axis in 0..3 {
+            if extent[axis] <= 0.0 {
+                continue;
+            }
+
+            let mut counts = [0usize; SAH_BINS];
+            let mut bounds: [Option<AABB>; SAH_BINS] = [None; SAH_BINS];
+
+            for (obj, c) in objects.iter().zip(&centroids) {
+                let bin = bin_of(*c, axis);
+                counts[bin] += 1;
+                let obj_aabb = obj.bounding_box();
+                bounds[bin] =
+                    Some(bounds[bin].map_or(obj_aabb, |x| AABB::from_aabbs(&x, &obj_aabb)));
+            }
+
+            // sweep from the right to get the area and count of every right hand side
+            let mut right_area = [0.0; SAH_BINS];
+            let mut right_count = [0usize; SAH_BINS];
+            let mut acc: Option<AABB> = None;
+            let mut count = 0;
+            for bin in (1..SAH_BINS).rev() {
+                if let Some(b) = bounds[bin] {
+                    acc = Some(acc.map_or(b, |x| AABB::from_aabbs(&x, &b)));
+                }
+                count += counts[bin];
+                right_area[bin] = acc.map_or(0.0, |x| x.surface_area());
+                right_count[bin] = count;
+            }
+
+            let mut acc: Option<AABB> = None;
+            let mut count = 0;
+            for split in 1..SAH_BINS {
+                if let Some(b) = bounds[split - 1] {
+                    acc = Some(acc.map_or(b, |x| AABB::from_aabbs(&x, &b)));
+                }
+                count += counts[split - 1];
+
+                if count == 0 || right_count[split] == 0 {
+                    continue;
+                }
+
+                let left_area = acc.map_or(0.0, |x| x.surface_area());
+                let cost =
+                    left_area.mul_add(count as f64, right_area[split] * right_count[split] as f64);
+
+                if best.is_none_or(|x| cost < x.0) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        let (_, axis, split) = best?;
+
+        // move everything left of the split plane to the front
+        let mut mid = 0;
+        for (k, c) in centroids.iter().enumerate() {
+            if bin_of(*c, axis) < split {
+                objects.swap(k, mid);
+                mid += 1;
+            }
+        }
+
+        Some(mid)
+    }
+
+    pub const fn stats(&self) -> BVHStats {
+        self.stats
+    }
+
+    // number of levels below and including this node, leaves count as part of their parent
+    pub const fn depth(&self) -> usize {
+        self.stats.depth
     }
 
     pub fn hittable_compare(a: &dyn Hittable, b: &dyn Hittable, axis: usize) -> Ordering {
@@ -110,4 +378,60 @@ impl Hittable for BVHNode {
     fn bounding_box(&self) -> AABB {
         self.bounding_box
     }
+
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        self.bounding_box.hit(r, i)
+            && (self.left.occluded(r, i, prng) || self.right.occluded(r, i, prng))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::bvh::{BVHBuildMethod, BVHNode};
+    use crate::hittable::Hittable;
+    use crate::hittable_list::HittableList;
+    use crate::interval::Interval;
+    use crate::lambertian::Lambertian;
+    use crate::ray::Ray;
+    use crate::sphere::Sphere;
+    use crate::types::{Color, P3};
+    use crate::utility::{make_prng_from, sample_unit_vector};
+    use std::sync::Arc;
+
+    #[test]
+    fn check_sah_and_median_agree() {
+        let mat = Arc::new(Lambertian::from_color(Color::ONE));
+        let mut prng = make_prng_from(11);
+
+        let make_list = || {
+            let mut list = HittableList::new();
+            for k in 0..200 {
+                let center = P3::new((k % 20) as f64, 0.0, (k / 20) as f64 * 3.0);
+                list.add(Box::new(Sphere::static_sphere(center, 0.4, mat.clone())));
+            }
+            list
+        };
+
+        let median = BVHNode::from_with(&mut make_list().objects, BVHBuildMethod::Median);
+        let sah = BVHNode::from_with(&mut make_list().objects, BVHBuildMethod::SAH);
+
+        assert_eq!(median.stats().node_count, 199);
+        assert_eq!(sah.stats().node_count, 199);
+        assert!(sah.stats().sah_cost <= median.stats().sah_cost);
+
+        for _ in 0..500 {
+            let origin = P3::new(10.0, 5.0, 15.0) + 3.0 * sample_unit_vector(&mut prng);
+            let ray = Ray::from(&origin, &sample_unit_vector(&mut prng), 0.0);
+
+            let a = median.hit(&ray, &Interval::casting_default(), &mut prng);
+            let b = sah.hit(&ray, &Interval::casting_default(), &mut prng);
+
+            match (a, b) {
+                (None, None) => {}
+                (Some(a), Some(b)) => assert!((a.t - b.t).abs() < 1e-9),
+                _ => panic!("sah and median trees disagree"),
+            }
+        }
+    }
 }
@@ -1,15 +1,18 @@
 use crate::hittable::Hittable;
+use crate::progress::{CancelToken, RenderProgress};
+use crate::progressive::{Accumulator, AdaptiveSampling, PixelStats, ProgressiveSettings};
 use crate::ray::Ray;
 use crate::renderer::{Renderer, SceneDetails};
 use crate::screen::Screen;
+use crate::tiles::{Tile, TileSettings};
 use crate::types::{Color, P3, V3};
-use crate::utility::{
-    linear_to_gamma, make_prng_from, random_double, sample_square, sample_unit_disc,
-};
-use glam::DVec3;
+use crate::utility::{derive_seed, make_prng_from, random_double, sample_square, sample_unit_disc};
 use rayon::iter::IntoParallelIterator;
 use rayon::iter::ParallelIterator;
 use smolprng::{JsfLarge, PRNG};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 
 pub struct Camera {
     image_height: usize,
@@ -29,19 +32,132 @@ pub struct Camera {
     defocus_disk_u: V3,
     defocus_disk_v: V3,
     background: Color,
+    seed: u64,
+    shutter_open: f64,
+    shutter_close: f64,
+    shutter_curve: ShutterCurve,
+    motion_blur: bool,
 }
 
 impl Camera {
+    pub fn from_config(config: &CameraConfig) -> Result<Self, CameraError> {
+        config.validate()?;
+
+        let CameraConfig {
+            image_width,
+            image_height,
+            samples_per_pixel,
+            max_depth,
+            fov,
+            look_from,
+            look_at,
+            v_up,
+            defocus_angle,
+            focus_dist,
+            background,
+            seed,
+            shutter_open,
+            shutter_close,
+            shutter_curve,
+            motion_blur,
+        } = *config;
+
+        let aspect_ratio = image_width as f64 / image_height as f64;
+
+        // Camera
+        let theta: f64 = fov.to_radians();
+        let h = (theta / 2.0).tan();
+        let viewport_height = 2.0 * h * focus_dist;
+        let viewport_width = viewport_height * aspect_ratio;
+        let camera_center = look_from;
+
+        // calculate u, v, w
+        let w = (look_from - look_at).normalize();
+        let u = v_up.cross(w).normalize();
+        let v = w.cross(u).normalize();
+
+        // calculate the vectors across and down
+        let viewport_u = viewport_width * u;
+        let viewport_v = -viewport_height * v;
+
+        // calculate the horizontal and vertical delta vector from pixel to pixel
+        let pixel_delta_u = viewport_u / image_width as f64;
+        let pixel_delta_v = viewport_v / image_height as f64;
+
+        // calculate the location of the upper left pixel
+        let viewport_upper_left =
+            camera_center - focus_dist * w - viewport_u / 2.0 - viewport_v / 2.0;
+        let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+
+        let defocus_radius = (defocus_angle / 2.0f64).to_radians().tan() * focus_dist;
+        let defocus_disk_u = u * defocus_radius;
+        let defocus_disk_v = v * defocus_radius;
+
+        Ok(Self {
+            image_height,
+            image_width,
+            samples_per_pixel,
+            fov,
+            defocus_angle,
+            focus_dist,
+            max_depth,
+            camera_center,
+            pixel00_loc,
+            pixel_delta_u,
+            pixel_delta_v,
+            u,
+            v,
+            w,
+            defocus_disk_u,
+            defocus_disk_v,
+            background,
+            seed,
+            shutter_open,
+            shutter_close,
+            shutter_curve,
+            motion_blur,
+        })
+    }
+
+    pub const fn image_width(&self) -> usize {
+        self.image_width
+    }
+
+    pub const fn image_height(&self) -> usize {
+        self.image_height
+    }
+
+    pub const fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
 
+    // every pixel and pass draws from its own stream hashed from the render seed, so the image does
+    // not depend on the thread scheduling and a progressive render can be split at any pass
+    fn pixel_prng(&self, i: usize, j: usize, pass: u64) -> PRNG<JsfLarge> {
+        make_prng_from(derive_seed(self.seed, &[i as u64, j as u64, pass]))
+    }
 
     pub fn render_pixel(&self, i: usize, j: usize, scene: &dyn Hittable, renderer: &dyn Renderer) -> Color {
-        let mut prng = make_prng_from(((i + 1) * (j + 1)) as u64);
+        self.sample_pixel(i, j, self.samples_per_pixel, 0, scene, renderer).sum / self.samples_per_pixel as f64
+    }
+
+    // `samples` samples of the pixel in the given pass
+    fn sample_pixel(
+        &self,
+        i: usize,
+        j: usize,
+        samples: usize,
+        pass: u64,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+    ) -> PixelStats {
+        let mut prng = self.pixel_prng(i, j, pass);
         let scene_details = SceneDetails {
             background: self.background,
         };
-        let mut pixel_color = Color::new(0.0, 0.0, 0.0);
+        let mut pixel = PixelStats::new();
 
-        for _ in 0..self.samples_per_pixel {
+        for _ in 0..samples {
             let r = self.get_ray(i, j, &mut prng);
             let mut in_flight = renderer.ray_color(&r, self.max_depth, scene, &scene_details, &mut prng);
 
@@ -57,41 +173,197 @@ impl Camera {
                 in_flight.z = 0.0;
             }
 
-            pixel_color += in_flight;
+            pixel.add(in_flight);
         }
 
-        pixel_color /= self.samples_per_pixel as f64;
-
-        pixel_color.x = linear_to_gamma(pixel_color.x);
-        pixel_color.y = linear_to_gamma(pixel_color.y);
-        pixel_color.z = linear_to_gamma(pixel_color.z);
-
-        pixel_color = (255.99 * pixel_color)
-            .floor()
-            .clamp(Color::ZERO, Color::splat(255.0));
+        pixel
+    }
 
-        pixel_color
+    pub fn render(&self, scene: &dyn Hittable, renderer: &dyn Renderer) -> Screen {
+        self.render_tiles(scene, renderer, &TileSettings::new(), &CancelToken::new(), |_| {})
     }
 
-    pub fn render(&self, scene: &dyn Hittable, renderer :&dyn Renderer) {
+    // renders the image, or its crop window, tile by tile, the workers take the tiles in the
+    // order of the settings and `on_progress` is called from them as each tile is finished, once
+    // `cancel` is set the workers stop and the pixels rendered so far are returned
+    pub fn render_tiles(
+        &self,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+        settings: &TileSettings,
+        cancel: &CancelToken,
+        on_progress: impl Fn(RenderProgress) + Sync,
+    ) -> Screen {
+        let start = Instant::now();
+        let tiles = settings.make_tiles(self.image_width, self.image_height);
+        let pixel_count = tiles.iter().map(Tile::pixel_count).sum();
+
         let mut screen = Screen::from(self.image_width, self.image_height);
+        screen.screen_data = vec![Color::ZERO; self.image_width * self.image_height];
+        // the image with the number of tiles and pixels written to it
+        let state = Mutex::new((screen, 0, 0));
+        let next_tile = AtomicUsize::new(0);
+
+        rayon::scope(|s| {
+            for _ in 0..rayon::current_num_threads() {
+                s.spawn(|_| {
+                    while let Some(tile) = tiles.get(next_tile.fetch_add(1, Ordering::Relaxed)) {
+                        let mut colors = Vec::with_capacity(tile.pixel_count());
+                        'tile: for j in tile.y0..tile.y1 {
+                            for i in tile.x0..tile.x1 {
+                                if cancel.is_cancelled() {
+                                    break 'tile;
+                                }
+                                colors.push(self.render_pixel(i, j, scene, renderer));
+                            }
+                        }
+
+                        let finished = colors.len() == tile.pixel_count();
+
+                        let mut guard = state.lock().unwrap();
+                        let (screen, tiles_done, pixels_done) = &mut *guard;
+                        *pixels_done += colors.len();
+                        *tiles_done += usize::from(finished);
+                        for (k, color) in colors.into_iter().enumerate() {
+                            screen.set(color, tile.x0 + k % tile.width(), tile.y0 + k / tile.width());
+                        }
+                        let (tiles_done, pixels_done) = (*tiles_done, *pixels_done);
+                        drop(guard);
+
+                        if !finished {
+                            return;
+                        }
+
+                        on_progress(RenderProgress {
+                            tile: *tile,
+                            tiles_done,
+                            tile_count: tiles.len(),
+                            pixels_done,
+                            pixel_count,
+                            elapsed: start.elapsed(),
+                        });
+                    }
+                });
+            }
+        });
 
-        let mut pixel_locs = Vec::new();
+        state.into_inner().unwrap().0
+    }
 
-        //Render
-        for j in 0..self.image_height {
-            for i in 0..self.image_width {
-                pixel_locs.push((i, j));
-            }
+    // adds one pass of `samples` samples per pixel to the accumulator, with adaptive sampling the
+    // pixels that have converged are skipped
+    pub fn render_pass(
+        &self,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+        accumulator: &mut Accumulator,
+        samples: usize,
+        adaptive: Option<&AdaptiveSampling>,
+    ) -> Result<(), CameraError> {
+        if !self.matches(accumulator) {
+            return Err(CameraError::AccumulatorMismatch);
         }
+        self.add_pass(scene, renderer, accumulator, samples, adaptive);
+        Ok(())
+    }
 
-        screen.screen_data = pixel_locs
+    // render_pass for an accumulator already known to match the camera
+    fn add_pass(
+        &self,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+        accumulator: &mut Accumulator,
+        samples: usize,
+        adaptive: Option<&AdaptiveSampling>,
+    ) {
+        let pass = accumulator.passes();
+        let pass_data = (0..self.image_width * self.image_height)
             .into_par_iter()
-            .map(|(i, j)| self.render_pixel(i, j, scene, renderer))
+            .map(|k| {
+                if accumulator.is_converged(k, adaptive) {
+                    return PixelStats::new();
+                }
+                let (i, j) = (k % self.image_width, k / self.image_width);
+                self.sample_pixel(i, j, samples, pass, scene, renderer)
+            })
             .collect();
 
-        let path = "output.png";
-        screen.write_png(path);
+        accumulator.add_pass(pass_data, samples);
+    }
+
+    const fn matches(&self, accumulator: &Accumulator) -> bool {
+        accumulator.width() == self.image_width
+            && accumulator.height() == self.image_height
+            && accumulator.seed() == self.seed
+    }
+
+    // renders passes until the samples per pixel or the time limit is reached, `snapshot` is
+    // called with the accumulated image as set by the settings
+    pub fn render_progressive(
+        &self,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+        settings: &ProgressiveSettings,
+        snapshot: impl FnMut(&Accumulator),
+    ) -> Accumulator {
+        let accumulator =
+            Accumulator::from(self.image_width, self.image_height, self.seed, settings);
+        self.continue_progressive(scene, renderer, settings, accumulator, snapshot)
+    }
+
+    // carries on a progressive render, e.g. from a checkpoint, with the same settings the result
+    // is the same as the uninterrupted render
+    pub fn resume_progressive(
+        &self,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+        settings: &ProgressiveSettings,
+        accumulator: Accumulator,
+        snapshot: impl FnMut(&Accumulator),
+    ) -> Result<Accumulator, CameraError> {
+        if !self.matches(&accumulator) {
+            return Err(CameraError::AccumulatorMismatch);
+        }
+        if !accumulator.matches_settings(settings) {
+            return Err(CameraError::ProgressiveSettingsMismatch);
+        }
+        Ok(self.continue_progressive(scene, renderer, settings, accumulator, snapshot))
+    }
+
+    fn continue_progressive(
+        &self,
+        scene: &dyn Hittable,
+        renderer: &dyn Renderer,
+        settings: &ProgressiveSettings,
+        mut accumulator: Accumulator,
+        mut snapshot: impl FnMut(&Accumulator),
+    ) -> Accumulator {
+        let start = Instant::now();
+        let mut last_snapshot = start;
+
+        while accumulator.samples() < self.samples_per_pixel {
+            if settings.time_limit.is_some_and(|x| start.elapsed() >= x) {
+                break;
+            }
+
+            let samples = settings
+                .samples_per_pass
+                .max(1)
+                .min(self.samples_per_pixel - accumulator.samples());
+            self.add_pass(scene, renderer, &mut accumulator, samples, settings.adaptive.as_ref());
+
+            if settings.snapshot_interval.is_none_or(|x| last_snapshot.elapsed() >= x) {
+                snapshot(&accumulator);
+                last_snapshot = Instant::now();
+            }
+
+            let pixels = self.image_width * self.image_height;
+            if settings.adaptive.is_some_and(|x| accumulator.converged_pixels(&x) == pixels) {
+                break;
+            }
+        }
+
+        accumulator
     }
 
     pub fn get_ray(&self, i: usize, j: usize, prng: &mut PRNG<JsfLarge>) -> Ray {
@@ -107,93 +379,385 @@ impl Camera {
         };
         let ray_direction = pixel_sample - ray_origin;
 
-        let ray_time = random_double(prng);
+        let ray_time = self.sample_time(j, prng);
 
         Ray::from(&ray_origin, &ray_direction, ray_time)
     }
 
+    // the time a ray of row j is traced at, spread over the shutter interval by the curve
+    fn sample_time(&self, j: usize, prng: &mut PRNG<JsfLarge>) -> f64 {
+        if !self.motion_blur {
+            return self.shutter_open;
+        }
+
+        let s = match self.shutter_curve {
+            ShutterCurve::Box => random_double(prng),
+            ShutterCurve::Triangle => 0.5 * (random_double(prng) + random_double(prng)),
+            ShutterCurve::Rolling { exposure } => {
+                let row = j as f64 / (self.image_height - 1).max(1) as f64;
+                (1.0 - exposure).mul_add(row, exposure * random_double(prng))
+            }
+        };
+
+        (self.shutter_close - self.shutter_open).mul_add(s, self.shutter_open)
+    }
+
     pub fn defocus_disk_sample(&self, prng: &mut PRNG<JsfLarge>) -> V3 {
         let p = sample_unit_disc(prng);
         self.camera_center + (p.x * self.defocus_disk_u) + (p.y * self.defocus_disk_v)
     }
 }
 
-pub fn initialize_camera(
-    cam_params: (
-        usize,
-        usize,
-        usize,
-        usize,
-        f64,
-        DVec3,
-        DVec3,
-        DVec3,
-        f64,
-        f64,
-        DVec3,
-    ),
-) -> Camera {
-    let (
-        image_width,
-        image_height,
-        samples_per_pixel,
-        max_depth,
-        fov,
-        look_from,
-        look_at,
-        v_up,
-        defocus_angle,
-        focus_dist,
-        background,
-    ) = cam_params;
-
-    let aspect_ratio = image_width as f64 / image_height as f64;
-
-    // Camera
-    let theta: f64 = fov.to_radians();
-    let h = (theta / 2.0).tan();
-    let viewport_height = 2.0 * h * focus_dist;
-    let viewport_width = viewport_height * aspect_ratio;
-    let camera_center = look_from;
-
-    // calculate u, v, w
-    let w = (look_from - look_at).normalize();
-    let u = v_up.cross(w).normalize();
-    let v = w.cross(u).normalize();
-
-    // calculate the vectors across and down
-    let viewport_u = viewport_width * u;
-    let viewport_v = -viewport_height * v;
-
-    // calculate the horizontal and vertical delta vector from pixel to pixel
-    let pixel_delta_u = viewport_u / image_width as f64;
-    let pixel_delta_v = viewport_v / image_height as f64;
-
-    // calculate the location of the upper left pixel
-    let viewport_upper_left = camera_center - focus_dist * w - viewport_u / 2.0 - viewport_v / 2.0;
-    let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
-
-    let defocus_radius = (defocus_angle / 2.0f64).to_radians().tan() * focus_dist;
-    let defocus_disk_u = u * defocus_radius;
-    let defocus_disk_v = v * defocus_radius;
-
-    Camera {
-        image_height,
-        image_width,
-        samples_per_pixel,
-        fov,
-        defocus_angle,
-        focus_dist,
-        max_depth,
-        camera_center,
-        pixel00_loc,
-        pixel_delta_u,
-        pixel_delta_v,
-        u,
-        v,
-        w,
-        defocus_disk_u,
-        defocus_disk_v,
-        background,
+#[derive(Copy, Clone)]
+pub struct CameraConfig {
+    pub image_width: usize,
+    pub image_height: usize,
+    pub samples_per_pixel: usize,
+    pub max_depth: usize,
+    pub fov: f64,
+    pub look_from: P3,
+    pub look_at: P3,
+    pub v_up: V3,
+    pub defocus_angle: f64,
+    pub focus_dist: f64,
+    pub background: Color,
+    pub seed: u64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    pub shutter_curve: ShutterCurve,
+    // without it every ray is traced at the time the shutter opens
+    pub motion_blur: bool,
+}
+
+// how the exposure is spread over the shutter interval, box weights it evenly, triangle ramps up
+// to the middle and back down, and a rolling shutter exposes every row for `exposure` of the
+// interval, starting with the top row at the opening and ending with the bottom row at the close
+#[derive(Copy, Clone)]
+pub enum ShutterCurve {
+    Box,
+    Triangle,
+    Rolling { exposure: f64 },
+}
+
+impl ShutterCurve {
+    // the rolling shutter exposes a quarter of the interval unless told otherwise
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "box" => Some(Self::Box),
+            "triangle" => Some(Self::Triangle),
+            "rolling" => Some(Self::Rolling { exposure: 0.25 }),
+            _ => None,
+        }
+    }
+}
+
+impl CameraConfig {
+    pub const fn new() -> Self {
+        Self {
+            image_width: 400,
+            image_height: 225,
+            samples_per_pixel: 100,
+            max_depth: 50,
+            fov: 90.0,
+            look_from: P3::ZERO,
+            look_at: P3::new(0.0, 0.0, -1.0),
+            v_up: V3::Y,
+            defocus_angle: 0.0,
+            focus_dist: 10.0,
+            background: Color::new(0.7, 0.8, 1.0),
+            seed: 0,
+            shutter_open: 0.0,
+            shutter_close: 1.0,
+            shutter_curve: ShutterCurve::Box,
+            motion_blur: true,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), CameraError> {
+        if self.image_width == 0 || self.image_height == 0 {
+            return Err(CameraError::ZeroImageSize);
+        }
+
+        if self.samples_per_pixel == 0 {
+            return Err(CameraError::ZeroSamples);
+        }
+
+        if !(self.fov > 0.0 && self.fov < 180.0) {
+            return Err(CameraError::InvalidFov(self.fov));
+        }
+
+        if !(self.focus_dist > 0.0 && self.focus_dist.is_finite()) {
+            return Err(CameraError::InvalidFocusDistance(self.focus_dist));
+        }
+
+        if !(0.0..180.0).contains(&self.defocus_angle) {
+            return Err(CameraError::InvalidDefocusAngle(self.defocus_angle));
+        }
+
+        let view = self.look_from - self.look_at;
+
+        if view.length_squared() <= 1e-12 || !view.is_finite() {
+            return Err(CameraError::LookFromEqualsLookAt);
+        }
+
+        if self.v_up.cross(view.normalize()).length_squared() <= 1e-12 {
+            return Err(CameraError::UpParallelToView);
+        }
+
+        if !(self.shutter_open.is_finite() && self.shutter_close.is_finite())
+            || self.shutter_close < self.shutter_open
+        {
+            return Err(CameraError::InvalidShutter(self.shutter_open, self.shutter_close));
+        }
+
+        if let ShutterCurve::Rolling { exposure } = self.shutter_curve
+            && !(exposure > 0.0 && exposure <= 1.0)
+        {
+            return Err(CameraError::InvalidRollingExposure(exposure));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug)]
+pub enum CameraError {
+    ZeroImageSize,
+    ZeroSamples,
+    InvalidFov(f64),
+    InvalidFocusDistance(f64),
+    InvalidDefocusAngle(f64),
+    LookFromEqualsLookAt,
+    UpParallelToView,
+    InvalidShutter(f64, f64),
+    InvalidRollingExposure(f64),
+    AccumulatorMismatch,
+    ProgressiveSettingsMismatch,
+}
+
+impl std::fmt::Display for CameraError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ZeroImageSize => write!(f, "image width and height must be non-zero"),
+            Self::ZeroSamples => write!(f, "samples per pixel must be non-zero"),
+            Self::InvalidFov(fov) => write!(f, "fov must be in (0, 180) degrees, got {fov}"),
+            Self::InvalidFocusDistance(dist) => {
+                write!(f, "focus distance must be positive, got {dist}")
+            }
+            Self::InvalidDefocusAngle(angle) => {
+                write!(f, "defocus angle must be in [0, 180) degrees, got {angle}")
+            }
+            Self::LookFromEqualsLookAt => write!(f, "look_from and look_at must be different points"),
+            Self::UpParallelToView => write!(f, "v_up must not be parallel to the view direction"),
+            Self::InvalidShutter(open, close) => {
+                write!(f, "the shutter must close after it opens, got {open} to {close}")
+            }
+            Self::InvalidRollingExposure(exposure) => {
+                write!(f, "rolling shutter exposure must be in (0, 1], got {exposure}")
+            }
+            Self::AccumulatorMismatch => {
+                write!(f, "the accumulated render has a different image size or seed")
+            }
+            Self::ProgressiveSettingsMismatch => {
+                write!(f, "the accumulated render used a different pass size or adaptive sampling")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CameraError {}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::camera::{Camera, CameraConfig, CameraError, ShutterCurve};
+    use crate::lambertian::Lambertian;
+    use crate::progressive::{Accumulator, CheckpointError, ProgressiveSettings};
+    use crate::renderer::FullRenderer;
+    use crate::sphere::Sphere;
+    use crate::types::{Color, P3, V3};
+    use crate::utility::make_prng_from;
+    use std::sync::Arc;
+
+    #[test]
+    fn check_invalid_configs_are_rejected() {
+        let config = CameraConfig {
+            image_width: 0,
+            ..CameraConfig::default()
+        };
+        assert!(matches!(Camera::from_config(&config), Err(CameraError::ZeroImageSize)));
+
+        let config = CameraConfig {
+            fov: 180.0,
+            ..CameraConfig::default()
+        };
+        assert!(matches!(Camera::from_config(&config), Err(CameraError::InvalidFov(_))));
+
+        let config = CameraConfig {
+            look_at: V3::ZERO,
+            ..CameraConfig::default()
+        };
+        assert!(matches!(Camera::from_config(&config), Err(CameraError::LookFromEqualsLookAt)));
+
+        let config = CameraConfig {
+            v_up: V3::Z,
+            ..CameraConfig::default()
+        };
+        assert!(matches!(Camera::from_config(&config), Err(CameraError::UpParallelToView)));
+
+        let config = CameraConfig {
+            shutter_open: 1.0,
+            shutter_close: 0.5,
+            ..CameraConfig::default()
+        };
+        assert!(matches!(Camera::from_config(&config), Err(CameraError::InvalidShutter(..))));
+
+        assert!(Camera::from_config(&CameraConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn check_progressive_render_matches_single_pass() {
+        let config = CameraConfig {
+            image_width: 8,
+            image_height: 6,
+            samples_per_pixel: 10,
+            ..CameraConfig::default()
+        };
+        let camera = Camera::from_config(&config).unwrap();
+        let mat = Arc::new(Lambertian::from_color(Color::splat(0.5)));
+        let scene = Sphere::static_sphere(P3::new(0.0, 0.0, -2.0), 1.0, mat);
+        let renderer = FullRenderer::new();
+
+        let screen = camera.render(&scene, &renderer);
+
+        // a single pass is the same render
+        let settings = ProgressiveSettings::new(10);
+        let accumulator = camera.render_progressive(&scene, &renderer, &settings, |_| {});
+        assert_eq!(accumulator.to_screen().screen_data, screen.screen_data);
+
+        // smaller passes stop at exactly the requested samples with a snapshot per pass
+        let mut snapshots = Vec::new();
+        let settings = ProgressiveSettings::new(4);
+        let accumulator = camera.render_progressive(&scene, &renderer, &settings, |x| {
+            snapshots.push(x.samples());
+        });
+        assert_eq!(snapshots, vec![4, 8, 10]);
+        assert_eq!(accumulator.passes(), 3);
+    }
+
+    #[test]
+    fn check_resumed_render_is_bit_identical() {
+        let config = CameraConfig {
+            image_width: 8,
+            image_height: 6,
+            samples_per_pixel: 12,
+            seed: 3,
+            ..CameraConfig::default()
+        };
+        let camera = Camera::from_config(&config).unwrap();
+        let mat = Arc::new(Lambertian::from_color(Color::splat(0.5)));
+        let scene = Sphere::static_sphere(P3::new(0.0, 0.0, -2.0), 1.0, mat);
+        let renderer = FullRenderer::new();
+        let settings = ProgressiveSettings::new(4);
+
+        let full = camera.render_progressive(&scene, &renderer, &settings, |_| {});
+
+        // interrupted after the first pass and picked up again from disk
+        let mut partial = Accumulator::from(8, 6, 3, &settings);
+        camera.render_pass(&scene, &renderer, &mut partial, 4, None).unwrap();
+        let path = std::env::temp_dir().join("spt_check_resumed_render.ckpt");
+        partial.save_checkpoint(&path).unwrap();
+        let loaded = Accumulator::load_checkpoint(&path).unwrap();
+
+        // a corrupt header claiming a huge image is rejected instead of overflowing
+        let mut data = std::fs::read(&path).unwrap();
+        data[8..24].fill(0xff);
+        std::fs::write(&path, data).unwrap();
+        let corrupt = Accumulator::load_checkpoint(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(corrupt, Err(CheckpointError::Format { .. })));
+
+        let resumed = camera
+            .resume_progressive(&scene, &renderer, &settings, loaded, |_| {})
+            .unwrap();
+        assert_eq!(resumed.samples(), 12);
+        assert_eq!(resumed.to_screen().screen_data, full.to_screen().screen_data);
+
+        let mut other_seed = Accumulator::from(8, 6, 4, &settings);
+        assert!(matches!(
+            camera.render_pass(&scene, &renderer, &mut other_seed, 4, None),
+            Err(CameraError::AccumulatorMismatch)
+        ));
+        assert!(matches!(
+            camera.resume_progressive(&scene, &renderer, &settings, other_seed, |_| {}),
+            Err(CameraError::AccumulatorMismatch)
+        ));
+
+        // the passes would not line up with the ones already accumulated
+        let other_pass = Accumulator::from(8, 6, 3, &ProgressiveSettings::new(3));
+        assert!(matches!(
+            camera.resume_progressive(&scene, &renderer, &settings, other_pass, |_| {}),
+            Err(CameraError::ProgressiveSettingsMismatch)
+        ));
+    }
+
+    #[test]
+    fn check_pixel_streams_are_distinct() {
+        let camera = Camera::from_config(&CameraConfig::default()).unwrap();
+        let first = |i, j, pass| camera.pixel_prng(i, j, pass).gen_u64();
+
+        assert_ne!(first(1, 3, 0), first(3, 1, 0));
+        assert_ne!(first(0, 5, 0), first(5, 0, 0));
+        assert_ne!(first(2, 2, 0), first(2, 2, 1));
+
+        let config = CameraConfig {
+            seed: 1,
+            ..CameraConfig::default()
+        };
+        let other = Camera::from_config(&config).unwrap();
+        assert_ne!(first(2, 2, 0), other.pixel_prng(2, 2, 0).gen_u64());
+    }
+
+    #[test]
+    fn check_shutter_curves() {
+        let times = |shutter_curve, motion_blur, j| {
+            let config = CameraConfig {
+                image_height: 11,
+                shutter_open: 2.0,
+                shutter_close: 4.0,
+                shutter_curve,
+                motion_blur,
+                ..CameraConfig::default()
+            };
+            let camera = Camera::from_config(&config).unwrap();
+            let mut prng = make_prng_from(8);
+            (0..2000)
+                .map(|_| camera.get_ray(0, j, &mut prng).time)
+                .collect::<Vec<f64>>()
+        };
+        let within = |x: &[f64], min: f64, max: f64| x.iter().all(|t| (min..=max).contains(t));
+        let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len() as f64;
+
+        let box_times = times(ShutterCurve::Box, true, 0);
+        assert!(within(&box_times, 2.0, 4.0));
+        assert!((mean(&box_times) - 3.0).abs() < 0.05);
+
+        // the triangle has less weight near the ends than the box
+        let triangle_times = times(ShutterCurve::Triangle, true, 0);
+        assert!(within(&triangle_times, 2.0, 4.0));
+        let near_ends = |x: &[f64]| x.iter().filter(|t| (**t - 3.0).abs() > 0.8).count();
+        assert!(near_ends(&triangle_times) < near_ends(&box_times) / 2);
+
+        let rolling = ShutterCurve::Rolling { exposure: 0.25 };
+        assert!(within(&times(rolling, true, 0), 2.0, 2.5));
+        assert!(within(&times(rolling, true, 10), 3.5, 4.0));
+
+        assert!(within(&times(ShutterCurve::Triangle, false, 5), 2.0, 2.0));
     }
 }
@@ -4,12 +4,18 @@ use crate::interval::Interval;
 use crate::ray::Ray;
 use crate::types::{P3, V3};
 use smolprng::{JsfLarge, PRNG};
+use std::sync::Arc;
 
 pub trait Hittable: Send + Sync {
     fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord>;
 
     fn bounding_box(&self) -> AABB;
 
+    // is anything hit in the interval, only needs a yes or no so no hit record is built
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        self.hit(r, i, prng).is_some()
+    }
+
     fn pdf_value(&self, _origin: &P3, _dir: &V3, _time: f64, _prng: &mut PRNG<JsfLarge>) -> f64 {
         0.0
     }
@@ -18,3 +24,26 @@ pub trait Hittable: Send + Sync {
         V3::X
     }
 }
+
+// lets the same object be shared between the world and the light list
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        (**self).hit(r, i, prng)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        (**self).bounding_box()
+    }
+
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        (**self).occluded(r, i, prng)
+    }
+
+    fn pdf_value(&self, origin: &P3, dir: &V3, time: f64, prng: &mut PRNG<JsfLarge>) -> f64 {
+        (**self).pdf_value(origin, dir, time, prng)
+    }
+
+    fn random(&self, origin: &P3, time: f64, prng: &mut PRNG<JsfLarge>) -> V3 {
+        (**self).random(origin, time, prng)
+    }
+}
@@ -56,6 +56,10 @@ impl Hittable for HittableList {
         hr
     }
 
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        self.objects.iter().any(|obj| obj.occluded(r, i, prng))
+    }
+
     fn bounding_box(&self) -> AABB {
         let mut list_aabb = AABB::new();
 
@@ -143,10 +143,22 @@ impl Material for DiffuseLight {
     }
 }
 
-struct Isotropic {
+pub struct Isotropic {
     tex: Arc<dyn Texture>,
 }
 
+impl Isotropic {
+    pub fn from_texture(tex: Arc<dyn Texture>) -> Self {
+        Self { tex }
+    }
+
+    pub fn from_color(c: Color) -> Self {
+        Self {
+            tex: Arc::new(SolidColor { albedo: c }),
+        }
+    }
+}
+
 impl Material for Isotropic {
     fn scatter(&self, _r: &Ray, rec: &HitRecord, _prng: &mut PRNG<JsfLarge>) -> Option<ScatterRay> {
         Some(ScatterRay::Scatter {
@@ -1,6 +1,7 @@
 #![warn(clippy::all, clippy::cargo, clippy::nursery, clippy::perf)]
 #[allow(clippy::unused)]
 pub mod aabb;
+pub mod animation;
 pub mod bvh;
 pub mod camera;
 pub mod hit_record;
@@ -8,18 +9,28 @@ pub mod hittable;
 pub mod hittable_list;
 pub mod interval;
 pub mod lambertian;
+pub mod linear_bvh;
 pub mod material;
 pub mod medium;
+pub mod mesh;
+pub mod obj;
 pub mod onb;
 pub mod pdf;
 pub mod perlin;
+pub mod progress;
+pub mod progressive;
 pub mod quad;
 pub mod ray;
 pub mod rotate;
+pub mod scene;
 pub mod screen;
 pub mod sphere;
 pub mod texture;
+pub mod tiles;
+pub mod tonemap;
+pub mod transform;
 pub mod translate;
+pub mod triangle;
 pub mod types;
 pub mod utility;
 pub mod renderer;
@@ -0,0 +1,365 @@
+use crate::aabb::AABB;
+use crate::bvh::{
+    BVHBuildMethod, BVHNode, BVHStats, INTERSECTION_COST, PARALLEL_BUILD_THRESHOLD, TRAVERSAL_COST,
+};
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use smolprng::{JsfLarge, PRNG};
+use std::sync::Arc;
+
+const MAX_LEAF_SIZE: usize = 2;
+
+// node of the flattened bvh, a leaf holds `count` objects starting at `offset`, an interior node
+// has its first child right after it and its second child at `offset`, `axis` is the split axis
+#[derive(Copy, Clone)]
+struct LinearNode {
+    aabb: AABB,
+    offset: u32,
+    count: u16,
+    axis: u8,
+}
+
+// bvh stored as one array in depth first order, traversed with an explicit stack
+pub struct LinearBVH {
+    nodes: Vec<LinearNode>,
+    objects: Vec<Arc<dyn Hittable>>,
+    stats: BVHStats,
+}
+
+impl LinearBVH {
+    pub fn from(obj_list: &mut Vec<Box<dyn Hittable>>) -> Self {
+        Self::from_with(obj_list, BVHBuildMethod::Median)
+    }
+
+    // takes the objects out of the list
+    pub fn from_with(obj_list: &mut Vec<Box<dyn Hittable>>, method: BVHBuildMethod) -> Self {
+        Self::build(obj_list, method, false)
+    }
+
+    // builds the two halves of large slices on the rayon pool, the nodes are the same as the ones
+    // from_with builds
+    pub fn par_from_with(obj_list: &mut Vec<Box<dyn Hittable>>, method: BVHBuildMethod) -> Self {
+        Self::build(obj_list, method, true)
+    }
+
+    fn build(
+        obj_list: &mut Vec<Box<dyn Hittable>>,
+        method: BVHBuildMethod,
+        parallel: bool,
+    ) -> Self {
+        let mut objects: Vec<Arc<dyn Hittable>> = obj_list.drain(..).map(Arc::from).collect();
+        let mut nodes = Vec::with_capacity(2 * objects.len());
+
+        let stats = if objects.is_empty() {
+            BVHStats {
+                node_count: 0,
+                depth: 0,
+                sah_cost: 0.0,
+            }
+        } else {
+            let (depth, cost) = Self::build_level(&mut nodes, &mut objects, 0, method, parallel);
+            let area = nodes[0].aabb.surface_area().max(f64::MIN_POSITIVE);
+            BVHStats {
+                node_count: nodes.len(),
+                depth,
+                sah_cost: cost / area,
+            }
+        };
+
+        Self {
+            nodes,
+            objects,
+            stats,
+        }
+    }
+
+    // returns the depth of the subtree and its sah cost scaled by the area of its bounding box
+    fn build_level(
+        nodes: &mut Vec<LinearNode>,
+        objects: &mut [Arc<dyn Hittable>],
+        offset: usize,
+        method: BVHBuildMethod,
+        parallel: bool,
+    ) -> (usize, f64) {
+        let node_index = nodes.len();
+        let parallel = parallel && objects.len() >= PARALLEL_BUILD_THRESHOLD;
+
+        let mut aabb = objects[0].bounding_box();
+        for obj in objects.iter() {
+            aabb = AABB::from_aabbs(&aabb, &obj.bounding_box());
+        }
+
+        if objects.len() <= MAX_LEAF_SIZE {
+            nodes.push(LinearNode {
+                aabb,
+                offset: offset as u32,
+                count: objects.len() as u16,
+                axis: 0,
+            });
+
+            return (
+                1,
+                aabb.surface_area() * objects.len() as f64 * INTERSECTION_COST,
+            );
+        }
+
+        let axis = aabb.longest_axis();
+        let mid = BVHNode::partition(objects, method, axis, parallel);
+
+        nodes.push(LinearNode {
+            aabb,
+            offset: 0,
+            count: 0,
+            axis: axis as u8,
+        });
+
+        let (first, second) = objects.split_at_mut(mid);
+
+        let ((first_depth, first_cost), (second_depth, second_cost)) = if parallel {
+            // the second subtree goes into its own array that is appended afterwards
+            let (first_result, (second_nodes, second_result)) = rayon::join(
+                || Self::build_level(nodes, first, offset, method, parallel),
+                || {
+                    let mut second_nodes = Vec::with_capacity(2 * second.len());
+                    let result = Self::build_level(
+                        &mut second_nodes,
+                        second,
+                        offset + mid,
+                        method,
+                        parallel,
+                    );
+                    (second_nodes, result)
+                },
+            );
+
+            let base = nodes.len() as u32;
+            nodes[node_index].offset = base;
+            nodes.extend(second_nodes.into_iter().map(|mut x| {
+                if x.count == 0 {
+                    x.offset += base;
+                }
+                x
+            }));
+
+            (first_result, second_result)
+        } else {
+            let first_result = Self::build_level(nodes, first, offset, method, parallel);
+            nodes[node_index].offset = nodes.len() as u32;
+            let second_result = Self::build_level(nodes, second, offset + mid, method, parallel);
+            (first_result, second_result)
+        };
+
+        let depth = 1 + first_depth.max(second_depth);
+        let cost = aabb
+            .surface_area()
+            .mul_add(TRAVERSAL_COST, first_cost + second_cost);
+        (depth, cost)
+    }
+
+    // walks the nodes calling `visit` on every object whose leaf the ray enters, near child
+    // first, `visit` returns the new upper bound of the interval or None to stop the walk
+    fn traverse(
+        &self,
+        r: &Ray,
+        i: &Interval,
+        mut visit: impl FnMut(&Arc<dyn Hittable>, f64) -> Option<f64>,
+    ) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut closest = i.max;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0usize);
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if !node.aabb.hit(r, &Interval::from(i.min, closest)) {
+                continue;
+            }
+
+            if node.count > 0 {
+                let start = node.offset as usize;
+                for obj in &self.objects[start..start + node.count as usize] {
+                    match visit(obj, closest) {
+                        Some(t) => closest = t,
+                        None => return,
+                    }
+                }
+            } else {
+                // visit the child on the side the ray comes from first
+                let (near, far) = if r.direction[node.axis as usize] < 0.0 {
+                    (node.offset as usize, node_index + 1)
+                } else {
+                    (node_index + 1, node.offset as usize)
+                };
+                stack.push(far);
+                stack.push(near);
+            }
+        }
+    }
+
+    pub const fn stats(&self) -> BVHStats {
+        self.stats
+    }
+
+    pub const fn depth(&self) -> usize {
+        self.stats.depth
+    }
+}
+
+impl Hittable for LinearBVH {
+    fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        let mut best = None;
+
+        self.traverse(r, i, |obj, closest| {
+            match obj.hit(r, &Interval::from(i.min, closest), prng) {
+                Some(record) => {
+                    let t = record.t;
+                    best = Some(record);
+                    Some(t)
+                }
+                None => Some(closest),
+            }
+        });
+
+        best
+    }
+
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        let mut found = false;
+
+        self.traverse(r, i, |obj, closest| {
+            found = obj.occluded(r, &Interval::from(i.min, closest), prng);
+            if found { None } else { Some(closest) }
+        });
+
+        found
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.nodes.first().map_or_else(AABB::new, |x| x.aabb)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::bvh::{BVHBuildMethod, BVHNode};
+    use crate::hittable::Hittable;
+    use crate::hittable_list::HittableList;
+    use crate::interval::Interval;
+    use crate::lambertian::Lambertian;
+    use crate::linear_bvh::LinearBVH;
+    use crate::ray::Ray;
+    use crate::sphere::Sphere;
+    use crate::types::{Color, P3};
+    use crate::utility::{make_prng_from, random_double_in_range, sample_unit_vector};
+    use std::sync::Arc;
+
+    #[test]
+    fn check_linear_bvh_matches_tree() {
+        let mat = Arc::new(Lambertian::from_color(Color::ONE));
+        let mut prng = make_prng_from(5);
+
+        let mut spheres = Vec::new();
+        for _ in 0..300 {
+            let center = P3::new(
+                random_double_in_range(&mut prng, -10.0, 10.0),
+                random_double_in_range(&mut prng, -10.0, 10.0),
+                random_double_in_range(&mut prng, -10.0, 10.0),
+            );
+            let radius = random_double_in_range(&mut prng, 0.1, 1.0);
+            spheres.push((center, radius));
+        }
+
+        let make_list = || {
+            let mut list = HittableList::new();
+            for (center, radius) in &spheres {
+                list.add(Box::new(Sphere::static_sphere(
+                    *center,
+                    *radius,
+                    mat.clone(),
+                )));
+            }
+            list
+        };
+
+        for method in [BVHBuildMethod::Median, BVHBuildMethod::SAH] {
+            let tree = BVHNode::from_with(&mut make_list().objects, method);
+            let linear = LinearBVH::from_with(&mut make_list().objects, method);
+
+            for _ in 0..1000 {
+                let origin = 15.0 * sample_unit_vector(&mut prng);
+                let ray = Ray::from(&origin, &sample_unit_vector(&mut prng), 0.0);
+
+                let a = tree.hit(&ray, &Interval::casting_default(), &mut prng);
+                let b = linear.hit(&ray, &Interval::casting_default(), &mut prng);
+
+                let is_hit = a.is_some();
+                assert_eq!(
+                    tree.occluded(&ray, &Interval::casting_default(), &mut prng),
+                    is_hit
+                );
+                assert_eq!(
+                    linear.occluded(&ray, &Interval::casting_default(), &mut prng),
+                    is_hit
+                );
+
+                match (a, b) {
+                    (None, None) => {}
+                    (Some(a), Some(b)) => assert!((a.t - b.t).abs() < 1e-9),
+                    _ => panic!("linear bvh and tree disagree"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_parallel_build_matches_sequential() {
+        let mat = Arc::new(Lambertian::from_color(Color::ONE));
+        let mut prng = make_prng_from(3);
+
+        let mut spheres = Vec::new();
+        for _ in 0..20_000 {
+            let center = P3::new(
+                random_double_in_range(&mut prng, -100.0, 100.0),
+                random_double_in_range(&mut prng, -10.0, 10.0),
+                random_double_in_range(&mut prng, -100.0, 100.0),
+            );
+            spheres.push(center);
+        }
+
+        let make_list = || {
+            let mut list = HittableList::new();
+            for center in &spheres {
+                list.add(Box::new(Sphere::static_sphere(*center, 0.5, mat.clone())));
+            }
+            list
+        };
+
+        for method in [BVHBuildMethod::Median, BVHBuildMethod::SAH] {
+            let sequential = LinearBVH::from_with(&mut make_list().objects, method);
+            let parallel = LinearBVH::par_from_with(&mut make_list().objects, method);
+
+            assert_eq!(sequential.nodes.len(), parallel.nodes.len());
+            for (a, b) in sequential.nodes.iter().zip(&parallel.nodes) {
+                assert_eq!(a.aabb.min, b.aabb.min);
+                assert_eq!(a.aabb.max, b.aabb.max);
+                assert_eq!((a.offset, a.count, a.axis), (b.offset, b.count, b.axis));
+            }
+            for (a, b) in sequential.objects.iter().zip(&parallel.objects) {
+                assert_eq!(a.bounding_box().min, b.bounding_box().min);
+            }
+
+            let sequential = BVHNode::from_with(&mut make_list().objects, method);
+            let parallel = BVHNode::par_from_with(&mut make_list().objects, method);
+            assert_eq!(sequential.stats().node_count, parallel.stats().node_count);
+            assert_eq!(sequential.stats().depth, parallel.stats().depth);
+            assert_eq!(sequential.stats().sah_cost, parallel.stats().sah_cost);
+        }
+    }
+}
@@ -2,19 +2,31 @@ use crate::aabb::AABB;
 use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
 use crate::interval::Interval;
+use crate::lambertian::Isotropic;
 use crate::material::Material;
 use crate::ray::Ray;
+use crate::texture::Texture;
 use crate::types::V3;
 use crate::utility::random_log_uniform;
 use smolprng::{JsfLarge, PRNG};
 use std::sync::Arc;
 
-struct ConstantMedium {
+pub struct ConstantMedium {
     boundary: Arc<dyn Hittable>,
     phase_function: Arc<dyn Material>,
     neg_inv_density: f64,
 }
 
+impl ConstantMedium {
+    pub fn from(boundary: Arc<dyn Hittable>, density: f64, tex: Arc<dyn Texture>) -> Self {
+        Self {
+            boundary,
+            phase_function: Arc::new(Isotropic::from_texture(tex)),
+            neg_inv_density: -1.0 / density,
+        }
+    }
+}
+
 impl Hittable for ConstantMedium {
     fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
         let universe = Interval::from(f64::MIN, f64::MAX);
@@ -0,0 +1,475 @@
+use crate::aabb::AABB;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle::{intersect_triangle, sample_triangle, shade_triangle};
+use crate::types::{P3, V3};
+use glam::DVec2;
+use smolprng::{JsfLarge, PRNG};
+use std::sync::Arc;
+
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Debug)]
+pub enum MeshError {
+    Empty,
+    NormalCount(usize, usize),
+    UvCount(usize, usize),
+    IndexOutOfRange(u32, usize),
+}
+
+impl std::fmt::Display for MeshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty => write!(f, "a mesh needs at least one triangle"),
+            Self::NormalCount(normals, positions) => {
+                write!(f, "{normals} normals given for {positions} positions")
+            }
+            Self::UvCount(uvs, positions) => write!(f, "{uvs} uvs given for {positions} positions"),
+            Self::IndexOutOfRange(index, positions) => {
+                write!(
+                    f,
+                    "vertex index {index} is out of range ({positions} positions)"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+// node of the internal bvh, a leaf holds `count` triangles starting at `offset` in the triangle
+// order, an interior node has its first child right after it and its second child at `offset`,
+// the first child holding the triangles lower along `axis`
+#[derive(Copy, Clone)]
+struct MeshNode {
+    aabb: AABB,
+    offset: u32,
+    count: u32,
+    axis: u8,
+}
+
+// triangles sharing vertex buffers, the whole mesh is a single hittable with its own bvh
+pub struct TriangleMesh {
+    positions: Vec<P3>,
+    normals: Vec<V3>,
+    uvs: Vec<DVec2>,
+    indices: Vec<[u32; 3]>,
+    mat: Arc<dyn Material>,
+    nodes: Vec<MeshNode>,
+    order: Vec<u32>,
+    area_cdf: Vec<f64>,
+    area: f64,
+}
+
+impl TriangleMesh {
+    // normals and uvs are per vertex and can be left empty, in which case the mesh is flat shaded
+    // and the uvs are the barycentric coordinates of each triangle
+    pub fn from(
+        positions: Vec<P3>,
+        normals: Vec<V3>,
+        uvs: Vec<DVec2>,
+        indices: Vec<[u32; 3]>,
+        mat: Arc<dyn Material>,
+    ) -> Result<Self, MeshError> {
+        if indices.is_empty() {
+            return Err(MeshError::Empty);
+        }
+        if !normals.is_empty() && normals.len() != positions.len() {
+            return Err(MeshError::NormalCount(normals.len(), positions.len()));
+        }
+        if !uvs.is_empty() && uvs.len() != positions.len() {
+            return Err(MeshError::UvCount(uvs.len(), positions.len()));
+        }
+        if let Some(index) = indices
+            .iter()
+            .flatten()
+            .find(|x| (**x as usize) >= positions.len())
+        {
+            return Err(MeshError::IndexOutOfRange(*index, positions.len()));
+        }
+
+        let mut mesh = Self {
+            positions,
+            normals,
+            uvs,
+            indices,
+            mat,
+            nodes: Vec::new(),
+            order: Vec::new(),
+            area_cdf: Vec::new(),
+            area: 0.0,
+        };
+
+        let mut area = 0.0;
+        mesh.area_cdf = (0..mesh.indices.len())
+            .map(|x| {
+                let [a, b, c] = mesh.vertices(x);
+                area += 0.5 * (b - a).cross(c - a).length();
+                area
+            })
+            .collect();
+        mesh.area = area;
+
+        mesh.build();
+        Ok(mesh)
+    }
+
+    pub const fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn vertices(&self, tri: usize) -> [P3; 3] {
+        self.indices[tri].map(|x| self.positions[x as usize])
+    }
+
+    fn triangle_aabb(&self, tri: usize) -> AABB {
+        let [a, b, c] = self.vertices(tri);
+        AABB::from_points(a.min(b).min(c), a.max(b).max(c))
+    }
+
+    fn centroid(&self, tri: usize) -> P3 {
+        let [a, b, c] = self.vertices(tri);
+        (a + b + c) / 3.0
+    }
+
+    fn build(&mut self) {
+        self.order = (0..self.indices.len() as u32).collect();
+        self.nodes = Vec::with_capacity(2 * self.indices.len());
+
+        if !self.indices.is_empty() {
+            self.build_level(0, self.indices.len());
+        }
+    }
+
+    fn build_level(&mut self, start: usize, end: usize) -> usize {
+        let node_index = self.nodes.len();
+
+        let mut aabb = self.triangle_aabb(self.order[start] as usize);
+        let mut centroid_min = P3::splat(f64::MAX);
+        let mut centroid_max = P3::splat(f64::MIN);
+
+        for tri in &self.order[start..end] {
+            aabb = AABB::from_aabbs(&aabb, &self.triangle_aabb(*tri as usize));
+            let centroid = self.centroid(*tri as usize);
+            centroid_min = centroid_min.min(centroid);
+            centroid_max = centroid_max.max(centroid);
+        }
+
+        let span = end - start;
+
+        if span <= MAX_LEAF_SIZE {
+            self.nodes.push(MeshNode {
+                aabb,
+                offset: start as u32,
+                count: span as u32,
+                axis: 0,
+            });
+            return node_index;
+        }
+
+        // split at the median centroid along the axis with the largest centroid spread
+        let axis = (centroid_max - centroid_min).max_position();
+        let mid = start + span / 2;
+
+        let mut order = std::mem::take(&mut self.order);
+        order[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            let x = self.centroid(*a as usize)[axis];
+            let y = self.centroid(*b as usize)[axis];
+            x.total_cmp(&y)
+        });
+        self.order = order;
+
+        self.nodes.push(MeshNode {
+            aabb,
+            offset: 0,
+            count: 0,
+            axis: axis as u8,
+        });
+
+        self.build_level(start, mid);
+        let second = self.build_level(mid, end);
+        self.nodes[node_index].offset = second as u32;
+
+        node_index
+    }
+
+    // walks the bvh calling `visit` on every triangle whose leaf the ray enters, the nearer child
+    // by the sign of the ray along the split axis first so the interval shrinks sooner, `visit`
+    // returns the new upper bound of the interval or None to stop the walk
+    fn traverse(&self, r: &Ray, i: &Interval, mut visit: impl FnMut(usize, f64) -> Option<f64>) {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut closest = i.max;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0usize);
+
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+
+            if !node.aabb.hit(r, &Interval::from(i.min, closest)) {
+                continue;
+            }
+
+            if node.count > 0 {
+                let start = node.offset as usize;
+                for tri in &self.order[start..start + node.count as usize] {
+                    match visit(*tri as usize, closest) {
+                        Some(t) => closest = t,
+                        None => return,
+                    }
+                }
+            } else if r.direction[node.axis as usize] < 0.0 {
+                stack.push(node_index + 1);
+                stack.push(node.offset as usize);
+            } else {
+                stack.push(node.offset as usize);
+                stack.push(node_index + 1);
+            }
+        }
+    }
+
+    fn closest_hit(&self, r: &Ray, i: &Interval) -> Option<(usize, f64, f64, f64)> {
+        let mut best = None;
+
+        self.traverse(r, i, |tri, closest| {
+            match intersect_triangle(r, &self.vertices(tri), &Interval::from(i.min, closest)) {
+                Some((t, b1, b2)) => {
+                    best = Some((tri, t, b1, b2));
+                    Some(t)
+                }
+                None => Some(closest),
+            }
+        });
+
+        best
+    }
+
+    fn any_hit(&self, r: &Ray, i: &Interval) -> bool {
+        let mut found = false;
+
+        self.traverse(r, i, |tri, closest| {
+            found = intersect_triangle(r, &self.vertices(tri), &Interval::from(i.min, closest))
+                .is_some();
+            if found { None } else { Some(closest) }
+        });
+
+        found
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        let (tri, t, b1, b2) = self.closest_hit(r, i)?;
+
+        let [a, b, c] = self.vertices(tri);
+        let geometric_normal = (b - a).cross(c - a).normalize();
+        let index = self.indices[tri];
+
+        let normals = if self.normals.is_empty() {
+            [geometric_normal; 3]
+        } else {
+            index.map(|x| self.normals[x as usize])
+        };
+
+        let uvs = if self.uvs.is_empty() {
+            [DVec2::ZERO, DVec2::X, DVec2::Y]
+        } else {
+            index.map(|x| self.uvs[x as usize])
+        };
+
+        Some(shade_triangle(
+            r,
+            t,
+            b1,
+            b2,
+            &geometric_normal,
+            &normals,
+            &uvs,
+            &self.mat,
+        ))
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.nodes.first().map_or_else(AABB::new, |x| x.aabb)
+    }
+
+    fn occluded(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> bool {
+        self.any_hit(r, i)
+    }
+
+    fn pdf_value(&self, origin: &P3, dir: &V3, time: f64, _prng: &mut PRNG<JsfLarge>) -> f64 {
+        let r = Ray::from(origin, dir, time);
+        let i = Interval::casting_default();
+
+        // `random` reaches a direction through every triangle it crosses, not just the closest
+        let mut pdf = 0.0;
+        self.traverse(&r, &i, |tri, closest| {
+            let vertices = self.vertices(tri);
+            if let Some((t, _, _)) = intersect_triangle(&r, &vertices, &i) {
+                let [a, b, c] = vertices;
+                let normal = (b - a).cross(c - a).normalize();
+
+                let dist_squared = t * t * dir.length_squared();
+                let cosine = (normal.dot(*dir) / dir.length()).abs();
+
+                pdf += dist_squared / (cosine * self.area);
+            }
+            Some(closest)
+        });
+
+        pdf
+    }
+
+    fn random(&self, origin: &P3, _time: f64, prng: &mut PRNG<JsfLarge>) -> V3 {
+        // pick a triangle proportional to its area
+        let target = prng.gen_f64() * self.area;
+        let tri = self
+            .area_cdf
+            .partition_point(|x| *x < target)
+            .min(self.indices.len() - 1);
+
+        sample_triangle(&self.vertices(tri), prng) - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::hittable::Hittable;
+    use crate::hittable_list::HittableList;
+    use crate::interval::Interval;
+    use crate::lambertian::Lambertian;
+    use crate::mesh::{MeshError, TriangleMesh};
+    use crate::ray::Ray;
+    use crate::triangle::Triangle;
+    use crate::types::{Color, P3, V3};
+    use crate::utility::{make_prng_from, sample_unit_vector};
+    use std::sync::Arc;
+
+    #[test]
+    fn check_mesh_matches_individual_triangles() {
+        let mat = Arc::new(Lambertian::from_color(Color::ONE));
+        let mut prng = make_prng_from(7);
+
+        // a bumpy 10 x 10 grid
+        let n = 10;
+        let mut positions = Vec::new();
+        for j in 0..=n {
+            for i in 0..=n {
+                let height = 0.3 * prng.gen_f64();
+                positions.push(P3::new(i as f64, height, j as f64));
+            }
+        }
+
+        let mut indices = Vec::new();
+        let mut list = HittableList::new();
+        for j in 0..n {
+            for i in 0..n {
+                let a = j * (n + 1) + i;
+                let quad = [a, a + 1, a + n + 2, a + n + 1];
+                for tri in [[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]] {
+                    indices.push(tri);
+                    let [a, b, c] = tri.map(|x| positions[x as usize]);
+                    list.add(Box::new(Triangle::new(a, b, c, mat.clone())));
+                }
+            }
+        }
+
+        let mesh = TriangleMesh::from(
+            positions.clone(),
+            Vec::new(),
+            Vec::new(),
+            indices,
+            mat.clone(),
+        )
+        .unwrap();
+
+        for _ in 0..500 {
+            let origin = P3::new(5.0, 3.0, 5.0) + 2.0 * sample_unit_vector(&mut prng);
+            let ray = Ray::from(&origin, &sample_unit_vector(&mut prng), 0.0);
+
+            let from_mesh = mesh.hit(&ray, &Interval::casting_default(), &mut prng);
+            let from_list = list.hit(&ray, &Interval::casting_default(), &mut prng);
+
+            match (from_mesh, from_list) {
+                (None, None) => {}
+                (Some(a), Some(b)) => assert!((a.t - b.t).abs() < 1e-9),
+                _ => panic!("mesh and triangle list disagree"),
+            }
+        }
+
+        let make = |normals, indices| {
+            TriangleMesh::from(positions.clone(), normals, Vec::new(), indices, mat.clone())
+        };
+        assert!(matches!(
+            make(Vec::new(), Vec::new()),
+            Err(MeshError::Empty)
+        ));
+        assert!(matches!(
+            make(vec![V3::Y], vec![[0, 1, 2]]),
+            Err(MeshError::NormalCount(1, _))
+        ));
+        assert!(matches!(
+            make(Vec::new(), vec![[0, 1, 500]]),
+            Err(MeshError::IndexOutOfRange(500, _))
+        ));
+    }
+
+    #[test]
+    fn check_closed_mesh_light_pdf_matches_sampling() {
+        let mat = Arc::new(Lambertian::from_color(Color::ONE));
+        let mut prng = make_prng_from(11);
+
+        // the cube [-1, 1]^3 seen from the z axis, only its front face is visible
+        let positions = (0..8)
+            .map(|x| P3::new(f64::from(x & 1), f64::from((x >> 1) & 1), f64::from(x >> 2)))
+            .map(|x| 2.0 * x - P3::ONE)
+            .collect();
+        let indices = vec![
+            [0, 1, 3],
+            [0, 3, 2],
+            [4, 5, 7],
+            [4, 7, 6],
+            [0, 1, 5],
+            [0, 5, 4],
+            [2, 3, 7],
+            [2, 7, 6],
+            [0, 2, 6],
+            [0, 6, 4],
+            [1, 3, 7],
+            [1, 7, 5],
+        ];
+        let cube = TriangleMesh::from(positions, Vec::new(), Vec::new(), indices, mat).unwrap();
+        let origin = P3::new(0.0, 0.0, 3.0);
+
+        // solid angle of a 2 x 2 square at distance 2 on its axis
+        let reference = 4.0 * (1.0 / (2.0 * 6f64.sqrt())).atan();
+
+        // half the directions from the light, half uniform, as the renderers mix them
+        let n = 20000;
+        let mut estimate = 0.0;
+        for _ in 0..n {
+            let dir = if prng.gen_f64() < 0.5 {
+                cube.random(&origin, 0.0, &mut prng)
+            } else {
+                sample_unit_vector(&mut prng)
+            };
+            let ray = Ray::from(&origin, &dir, 0.0);
+            if cube
+                .hit(&ray, &Interval::casting_default(), &mut prng)
+                .is_some()
+            {
+                let light_pdf = cube.pdf_value(&origin, &dir.normalize(), 0.0, &mut prng);
+                estimate += 1.0 / 0.5f64.mul_add(light_pdf, 0.5 / (4.0 * std::f64::consts::PI));
+            }
+        }
+        estimate /= f64::from(n);
+
+        assert!((estimate / reference - 1.0).abs() < 0.03);
+    }
+}
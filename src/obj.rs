@@ -0,0 +1,578 @@
+use crate::hittable_list::HittableList;
+use crate::lambertian::{Dielectric, DiffuseLight, Lambertian, Metal};
+use crate::material::Material;
+use crate::mesh::{MeshError, TriangleMesh};
+use crate::texture::ImageTexture;
+use crate::types::{Color, P3, V3};
+use glam::DVec2;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    Mesh {
+        path: PathBuf,
+        group: String,
+        source: MeshError,
+    },
+}
+
+impl std::fmt::Display for ObjError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+            Self::Mesh {
+                path,
+                group,
+                source,
+            } => write!(f, "{}: group `{group}`: {source}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Mesh { source, .. } => Some(source),
+            Self::Parse { .. } => None,
+        }
+    }
+}
+
+// a run of faces sharing a group and a material
+pub struct ObjGroup {
+    pub name: String,
+    pub material: String,
+    pub mesh: Arc<TriangleMesh>,
+    pub is_emissive: bool,
+}
+
+pub struct ObjModel {
+    pub groups: Vec<ObjGroup>,
+}
+
+impl ObjModel {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ObjError> {
+        let path = path.as_ref();
+        Self::parse(&read_file(path)?, path)
+    }
+
+    // `path` is only used in errors and to find the material libraries next to it
+    pub fn parse(source: &str, path: &Path) -> Result<Self, ObjError> {
+        let mut parser = ObjParser::new(path);
+
+        for (line_number, line) in source.lines().enumerate() {
+            parser.parse_line(line).map_err(|message| ObjError::Parse {
+                path: path.to_path_buf(),
+                line: line_number + 1,
+                message,
+            })?;
+        }
+
+        parser.finish()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.groups.iter().map(|x| x.mesh.triangle_count()).sum()
+    }
+
+    // every group goes into the world, emissive groups are also added to the lights
+    pub fn into_lists(self) -> (HittableList, HittableList) {
+        let mut world = HittableList::new();
+        let mut lights = HittableList::new();
+
+        for group in self.groups {
+            if group.is_emissive {
+                lights.add(Box::new(group.mesh.clone()));
+            }
+            world.add(Box::new(group.mesh));
+        }
+
+        (world, lights)
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    std::fs::read_to_string(path).map_err(|source| ObjError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn parse_floats<const N: usize>(args: &[&str], keyword: &str) -> Result<[f64; N], String> {
+    if args.len() < N {
+        return Err(format!(
+            "`{keyword}` expects {N} numbers, got {}",
+            args.len()
+        ));
+    }
+
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| format!("`{arg}` is not a number in `{keyword}`"))?;
+    }
+    Ok(values)
+}
+
+// a single value is used for all three channels
+fn parse_color(args: &[&str], keyword: &str) -> Result<Color, String> {
+    if args.len() == 1 {
+        return Ok(Color::splat(parse_floats::<1>(args, keyword)?[0]));
+    }
+    Ok(Color::from_array(parse_floats(args, keyword)?))
+}
+
+// obj indices are one based and negative indices count back from the last element
+fn resolve_index(arg: &str, len: usize, kind: &str) -> Result<usize, String> {
+    let index: i64 = arg
+        .parse()
+        .map_err(|_| format!("`{arg}` is not a valid {kind} index"))?;
+
+    let resolved = if index > 0 {
+        index - 1
+    } else {
+        len as i64 + index
+    };
+
+    if index == 0 || resolved < 0 || resolved >= len as i64 {
+        return Err(format!(
+            "{kind} index {index} is out of range ({len} defined)"
+        ));
+    }
+
+    Ok(resolved as usize)
+}
+
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+// faces collected for the current group and material
+struct Segment {
+    name: String,
+    material: String,
+    vertices: Vec<VertexKey>,
+    lookup: HashMap<VertexKey, u32>,
+    indices: Vec<[u32; 3]>,
+}
+
+impl Segment {
+    fn new(name: &str, material: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            material: material.to_string(),
+            vertices: Vec::new(),
+            lookup: HashMap::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn vertex(&mut self, key: VertexKey) -> u32 {
+        *self.lookup.entry(key).or_insert_with(|| {
+            self.vertices.push(key);
+            (self.vertices.len() - 1) as u32
+        })
+    }
+}
+
+struct ObjParser {
+    path: PathBuf,
+    directory: PathBuf,
+    positions: Vec<P3>,
+    uvs: Vec<DVec2>,
+    normals: Vec<V3>,
+    materials: HashMap<String, MtlMaterial>,
+    group: String,
+    segment: Segment,
+    finished: Vec<Segment>,
+}
+
+impl ObjParser {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            directory: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+            materials: HashMap::new(),
+            group: "default".to_string(),
+            segment: Segment::new("default", ""),
+            finished: Vec::new(),
+        }
+    }
+
+    fn start_segment(&mut self, material: &str) {
+        let next = Segment::new(&self.group, material);
+        let done = std::mem::replace(&mut self.segment, next);
+        if !done.indices.is_empty() {
+            self.finished.push(done);
+        }
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+
+        let Some(keyword) = tokens.next() else {
+            return Ok(());
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(&args, keyword)?;
+                self.positions.push(P3::new(x, y, z));
+            }
+            "vt" => {
+                let u = parse_floats::<1>(&args, keyword)?[0];
+                let v = if args.len() > 1 {
+                    parse_floats::<1>(&args[1..], keyword)?[0]
+                } else {
+                    0.0
+                };
+                self.uvs.push(DVec2::new(u, v));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(&args, keyword)?;
+                self.normals.push(V3::new(x, y, z));
+            }
+            "f" => self.parse_face(&args)?,
+            "g" | "o" => {
+                self.group = if args.is_empty() {
+                    "default".to_string()
+                } else {
+                    args.join(" ")
+                };
+                let material = self.segment.material.clone();
+                self.start_segment(&material);
+            }
+            "usemtl" => {
+                let name = args.join(" ");
+                if !self.materials.contains_key(&name) {
+                    return Err(format!("material `{name}` is not defined"));
+                }
+                self.start_segment(&name);
+            }
+            "mtllib" => {
+                for file in args {
+                    let path = self.directory.join(file);
+                    let materials = parse_mtl(&path).map_err(|e| e.to_string())?;
+                    self.materials.extend(materials);
+                }
+            }
+            // everything else, smoothing groups, lines, points, free-form geometry and the
+            // rest, does not affect the triangles
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn parse_face(&mut self, args: &[&str]) -> Result<(), String> {
+        if args.len() < 3 {
+            return Err(format!(
+                "a face needs at least 3 vertices, got {}",
+                args.len()
+            ));
+        }
+
+        let mut face = Vec::with_capacity(args.len());
+
+        for arg in args {
+            let mut parts = arg.split('/');
+
+            let position = resolve_index(parts.next().unwrap_or(""), self.positions.len(), "v")?;
+            let uv = match parts.next() {
+                None | Some("") => None,
+                Some(x) => Some(resolve_index(x, self.uvs.len(), "vt")?),
+            };
+            let normal = match parts.next() {
+                None | Some("") => None,
+                Some(x) => Some(resolve_index(x, self.normals.len(), "vn")?),
+            };
+
+            face.push(self.segment.vertex((position, uv, normal)));
+        }
+
+        // polygons are triangulated as a fan around the first vertex
+        for k in 1..face.len() - 1 {
+            self.segment.indices.push([face[0], face[k], face[k + 1]]);
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<ObjModel, ObjError> {
+        self.start_segment("");
+
+        let default_material: Arc<dyn Material> =
+            Arc::new(Lambertian::from_color(Color::new(0.5, 0.5, 0.5)));
+
+        // segments using the same material share it, so textures are only loaded once
+        let mut built: HashMap<String, Arc<dyn Material>> = HashMap::new();
+
+        let groups = self
+            .finished
+            .iter()
+            .map(|segment| {
+                let positions = segment
+                    .vertices
+                    .iter()
+                    .map(|x| self.positions[x.0])
+                    .collect();
+
+                // only use normals and uvs if every vertex of the segment has them
+                let normals = if segment.vertices.iter().all(|x| x.2.is_some()) {
+                    segment
+                        .vertices
+                        .iter()
+                        .filter_map(|x| x.2.map(|n| self.normals[n]))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                let uvs = if segment.vertices.iter().all(|x| x.1.is_some()) {
+                    segment
+                        .vertices
+                        .iter()
+                        .filter_map(|x| x.1.map(|n| self.uvs[n]))
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+
+                let (mat, is_emissive) = self.materials.get(&segment.material).map_or_else(
+                    || (default_material.clone(), false),
+                    |x| {
+                        let mat = built
+                            .entry(segment.material.clone())
+                            .or_insert_with(|| x.to_material());
+                        (mat.clone(), x.is_emissive())
+                    },
+                );
+
+                let mesh =
+                    TriangleMesh::from(positions, normals, uvs, segment.indices.clone(), mat)
+                        .map_err(|source| ObjError::Mesh {
+                            path: self.path.clone(),
+                            group: segment.name.clone(),
+                            source,
+                        })?;
+
+                Ok(ObjGroup {
+                    name: segment.name.clone(),
+                    material: segment.material.clone(),
+                    mesh: Arc::new(mesh),
+                    is_emissive,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ObjModel { groups })
+    }
+}
+
+// the subset of the mtl format that maps onto our materials
+#[derive(Clone)]
+pub struct MtlMaterial {
+    pub diffuse: Color,
+    pub specular: Color,
+    pub emission: Color,
+    pub shininess: f64,
+    pub refraction_index: f64,
+    pub dissolve: f64,
+    pub illum: u32,
+    pub diffuse_map: Option<PathBuf>,
+}
+
+impl MtlMaterial {
+    pub const fn new() -> Self {
+        Self {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::ZERO,
+            emission: Color::ZERO,
+            shininess: 0.0,
+            refraction_index: 1.5,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None,
+        }
+    }
+
+    pub fn is_emissive(&self) -> bool {
+        self.emission.max_element() > 0.0
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.dissolve < 1.0 || matches!(self.illum, 4 | 6 | 7)
+    }
+
+    pub fn is_metallic(&self) -> bool {
+        self.specular.max_element() > 0.0 && (self.illum == 3 || self.diffuse.max_element() <= 0.0)
+    }
+
+    // emission wins over transparency, which wins over reflection, everything else is diffuse
+    pub fn to_material(&self) -> Arc<dyn Material> {
+        if self.is_emissive() {
+            Arc::new(DiffuseLight::from_color(self.emission))
+        } else if self.is_transparent() {
+            Arc::new(Dielectric {
+                refraction_index: self.refraction_index,
+            })
+        } else if self.is_metallic() {
+            // blinn-phong exponent to a roughness
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt().clamp(0.0, 1.0);
+            Arc::new(Metal {
+                albedo: self.specular,
+                fuzz,
+            })
+        } else if let Some(map) = &self.diffuse_map {
+            Arc::new(Lambertian::from_texture(Arc::new(ImageTexture::new(
+                &map.to_string_lossy(),
+            ))))
+        } else {
+            Arc::new(Lambertian::from_color(self.diffuse))
+        }
+    }
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn parse_mtl(path: &Path) -> Result<HashMap<String, MtlMaterial>, ObjError> {
+    let source = read_file(path)?;
+    let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, MtlMaterial)> = None;
+
+    for (line_number, line) in source.lines().enumerate() {
+        let error = |message: String| ObjError::Parse {
+            path: path.to_path_buf(),
+            line: line_number + 1,
+            message,
+        };
+
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let args: Vec<&str> = tokens.collect();
+
+        if keyword == "newmtl" {
+            if let Some((name, mat)) = current.take() {
+                materials.insert(name, mat);
+            }
+            current = Some((args.join(" "), MtlMaterial::new()));
+            continue;
+        }
+
+        let Some((_, mat)) = current.as_mut() else {
+            return Err(error(format!("`{keyword}` before any `newmtl`")));
+        };
+
+        match keyword {
+            "Kd" => mat.diffuse = parse_color(&args, keyword).map_err(error)?,
+            "Ks" => mat.specular = parse_color(&args, keyword).map_err(error)?,
+            "Ke" => mat.emission = parse_color(&args, keyword).map_err(error)?,
+            "Ns" => mat.shininess = parse_floats::<1>(&args, keyword).map_err(error)?[0],
+            "Ni" => mat.refraction_index = parse_floats::<1>(&args, keyword).map_err(error)?[0],
+            "d" => mat.dissolve = parse_floats::<1>(&args, keyword).map_err(error)?[0],
+            "Tr" => mat.dissolve = 1.0 - parse_floats::<1>(&args, keyword).map_err(error)?[0],
+            "illum" => {
+                mat.illum = args
+                    .first()
+                    .and_then(|x| x.parse().ok())
+                    .ok_or_else(|| error("`illum` expects an integer".to_string()))?;
+            }
+            "map_Kd" => {
+                // options before the file name are not supported, the file is the last argument
+                let file = args
+                    .last()
+                    .ok_or_else(|| error("`map_Kd` expects a file name".to_string()))?;
+                let map = directory.join(file);
+                if !map.is_file() {
+                    return Err(error(format!("image `{}` does not exist", map.display())));
+                }
+                mat.diffuse_map = Some(map);
+            }
+            // the rest of the format has no equivalent in our materials
+            _ => {}
+        }
+    }
+
+    if let Some((name, mat)) = current.take() {
+        materials.insert(name, mat);
+    }
+
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::obj::{ObjError, ObjModel};
+    use crate::types::Color;
+    use std::path::Path;
+
+    #[test]
+    fn check_obj_with_materials_and_errors() {
+        let dir = std::env::temp_dir().join(format!("spt_obj_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mtl = "newmtl white\nKd 0.8 0.8 0.8\nnewmtl lamp\nKe 4\n";
+        std::fs::write(dir.join("box.mtl"), mtl).unwrap();
+        std::fs::write(dir.join("bad.mtl"), "newmtl wood\nmap_Kd wood.png\n").unwrap();
+
+        let obj = "mtllib box.mtl\n\
+                   v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nvp 0.5 0.5\n\
+                   g floor\nusemtl white\nf 1//1 2//1 3//1 4//1\n\
+                   g light\nusemtl lamp\nshadow_obj box.obj\nf -4 -3 -2\n";
+        std::fs::write(dir.join("box.obj"), obj).unwrap();
+
+        let model = ObjModel::load(dir.join("box.obj"));
+        let materials = crate::obj::parse_mtl(&dir.join("box.mtl"));
+        let missing = crate::obj::parse_mtl(&dir.join("bad.mtl"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let model = model.unwrap();
+        assert_eq!(model.groups.len(), 2);
+        assert_eq!(model.triangle_count(), 3);
+        assert!(!model.groups[0].is_emissive);
+        assert!(model.groups[1].is_emissive);
+        assert_eq!(materials.unwrap()["lamp"].emission, Color::splat(4.0));
+        assert!(
+            matches!(missing, Err(ObjError::Parse { line: 2, message, .. }) if message.contains("wood.png"))
+        );
+
+        let (world, lights) = model.into_lists();
+        assert_eq!(world.objects.len(), 2);
+        assert_eq!(lights.objects.len(), 1);
+
+        let error = ObjModel::parse("v 0 0 0\nv 1 0 0\nf 1 2 3\n", Path::new("bad.obj"));
+        assert!(matches!(error, Err(ObjError::Parse { line: 3, .. })));
+    }
+}
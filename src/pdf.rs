@@ -10,6 +10,25 @@ pub enum PDF {
     Sphere {},
     Cosine { uvw: ONB },
     Hittable { obj: Arc<dyn Hittable>, origin: P3 },
+    Mixture { first: Box<Self>, second: Box<Self>, weight: f64 },
+}
+
+// how to weight samples drawn from one strategy of a mixture against the other
+#[derive(Copy, Clone)]
+pub enum MISHeuristic {
+    Balance,
+    Power,
+}
+
+impl MISHeuristic {
+    pub fn weight(&self, pdf: f64, other_pdf: f64) -> f64 {
+        let (a, b) = match self {
+            Self::Balance => (pdf, other_pdf),
+            Self::Power => (pdf * pdf, other_pdf * other_pdf),
+        };
+
+        if a + b <= 0.0 { 0.0 } else { a / (a + b) }
+    }
 }
 
 impl PDF {
@@ -24,6 +43,15 @@ impl PDF {
         }
     }
 
+    // weight is the probability of drawing from the first pdf
+    pub fn mixture(first: Self, second: Self, weight: f64) -> Self {
+        Self::Mixture {
+            first: Box::new(first),
+            second: Box::new(second),
+            weight,
+        }
+    }
+
     pub const fn sphere() -> Self {
         Self::Sphere {}
     }
@@ -36,6 +64,15 @@ impl PDF {
                 (cos_theta / std::f64::consts::PI).max(0.0)
             }
             Self::Hittable { obj, origin } => obj.pdf_value(origin, dir, time, prng),
+            Self::Mixture {
+                first,
+                second,
+                weight,
+            } => {
+                let first_value = first.value(dir, time, prng);
+                let second_value = second.value(dir, time, prng);
+                weight.mul_add(first_value, (1.0 - weight) * second_value)
+            }
         }
     }
 
@@ -44,6 +81,73 @@ impl PDF {
             Self::Sphere {} => sample_unit_vector(prng),
             Self::Cosine { uvw } => uvw.transform(&sample_cosine_direction(prng)),
             Self::Hittable { obj, origin } => obj.random(origin, time, prng),
+            Self::Mixture {
+                first,
+                second,
+                weight,
+            } => {
+                if prng.gen_f64() < *weight {
+                    first.generate(time, prng)
+                } else {
+                    second.generate(time, prng)
+                }
+            }
+        }
+    }
+
+    // draws a direction and returns it with the density to divide the sample by, for a mixture
+    // this is the density of the strategy that was picked divided by its heuristic weight
+    // (one sample MIS), with the balance heuristic this is just the mixture density
+    pub fn generate_mis(
+        &self,
+        heuristic: &MISHeuristic,
+        time: f64,
+        prng: &mut PRNG<JsfLarge>,
+    ) -> (V3, f64) {
+        match self {
+            Self::Mixture {
+                first,
+                second,
+                weight,
+            } => {
+                let (picked, other, picked_weight) = if prng.gen_f64() < *weight {
+                    (first, second, *weight)
+                } else {
+                    (second, first, 1.0 - weight)
+                };
+
+                let dir = picked.generate(time, prng);
+                let picked_pdf = picked_weight * picked.value(&dir, time, prng);
+                let other_pdf = (1.0 - picked_weight) * other.value(&dir, time, prng);
+                let mis_weight = heuristic.weight(picked_pdf, other_pdf);
+
+                if mis_weight <= 0.0 {
+                    (dir, 0.0)
+                } else {
+                    (dir, picked_pdf / mis_weight)
+                }
+            }
+            _ => {
+                let dir = self.generate(time, prng);
+                let pdf = self.value(&dir, time, prng);
+                (dir, pdf)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::pdf::MISHeuristic;
+
+    #[test]
+    fn check_heuristic_weights_sum_to_one() {
+        for heuristic in [MISHeuristic::Balance, MISHeuristic::Power] {
+            let a = heuristic.weight(0.3, 1.7);
+            let b = heuristic.weight(1.7, 0.3);
+
+            assert!((a + b - 1.0).abs() < 1e-12);
         }
     }
 }
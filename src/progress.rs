@@ -0,0 +1,103 @@
+use crate::tiles::Tile;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+// how far a tiled render has got, handed out after every finished tile
+#[derive(Copy, Clone)]
+pub struct RenderProgress {
+    pub tile: Tile,
+    pub tiles_done: usize,
+    pub tile_count: usize,
+    pub pixels_done: usize,
+    pub pixel_count: usize,
+    pub elapsed: Duration,
+}
+
+impl RenderProgress {
+    pub fn fraction(&self) -> f64 {
+        self.pixels_done as f64 / self.pixel_count.max(1) as f64
+    }
+
+    // extrapolated from the pixels done so far, None before the first one
+    pub fn remaining(&self) -> Option<Duration> {
+        if self.pixels_done == 0 {
+            return None;
+        }
+        let left = (self.pixel_count - self.pixels_done) as f64 / self.pixels_done as f64;
+        Some(self.elapsed.mul_f64(left))
+    }
+}
+
+// shared flag that stops a render, the workers check it before every pixel
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::camera::{Camera, CameraConfig};
+    use crate::hittable_list::HittableList;
+    use crate::progress::CancelToken;
+    use crate::renderer::FullRenderer;
+    use crate::tiles::{TileOrder, TileSettings};
+    use crate::types::Color;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn check_cancelled_render_keeps_finished_tiles() {
+        let config = CameraConfig {
+            image_width: 32,
+            image_height: 32,
+            samples_per_pixel: 2,
+            ..CameraConfig::default()
+        };
+        let camera = Camera::from_config(&config).unwrap();
+        let scene = HittableList::new();
+        let settings = TileSettings {
+            tile_size: 8,
+            order: TileOrder::Scanline,
+            crop: None,
+        };
+
+        // a single worker finishes the first tile, reports it and sees the cancel right after
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let cancel = CancelToken::new();
+        let reports = AtomicUsize::new(0);
+        let screen = pool.install(|| {
+            camera.render_tiles(&scene, &FullRenderer::new(), &settings, &cancel, |x| {
+                reports.fetch_add(1, Ordering::Relaxed);
+                assert_eq!((x.tiles_done, x.pixels_done), (1, 64));
+                assert!(x.remaining().is_some());
+                cancel.cancel();
+            })
+        });
+        assert_eq!(reports.into_inner(), 1);
+
+        // only the first tile has been rendered, the rest stays black
+        for y in 0..32 {
+            for x in 0..32 {
+                assert_eq!(screen.get(x, y) != Color::ZERO, x < 8 && y < 8);
+            }
+        }
+    }
+}
@@ -0,0 +1,448 @@
+use crate::screen::Screen;
+use crate::types::Color;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const CHECKPOINT_MAGIC: &[u8; 8] = b"SPTCKPT4";
+
+// width, height, seed, samples, passes, samples per pass, adaptive flag, threshold, min samples
+const CHECKPOINT_HEADER_WORDS: usize = 9;
+
+// when to stop a progressive render and how often to hand out snapshots, the render always stops
+// once the camera's samples per pixel are reached
+#[derive(Copy, Clone)]
+pub struct ProgressiveSettings {
+    pub samples_per_pass: usize,
+    pub time_limit: Option<Duration>,
+    // None gives a snapshot after every pass
+    pub snapshot_interval: Option<Duration>,
+    pub adaptive: Option<AdaptiveSampling>,
+}
+
+impl ProgressiveSettings {
+    pub const fn new(samples_per_pass: usize) -> Self {
+        Self {
+            samples_per_pass,
+            time_limit: None,
+            snapshot_interval: None,
+            adaptive: None,
+        }
+    }
+}
+
+// a pixel stops being sampled once the 95% confidence interval of its mean luminance is within
+// `threshold` of the mean, after at least `min_samples` samples
+#[derive(Copy, Clone, PartialEq)]
+pub struct AdaptiveSampling {
+    pub threshold: f64,
+    pub min_samples: u64,
+}
+
+impl AdaptiveSampling {
+    pub const fn new(threshold: f64) -> Self {
+        Self {
+            threshold,
+            min_samples: 16,
+        }
+    }
+}
+
+// the samples of one pixel, the sum of the radiance and the running mean and variance of the
+// luminance (Welford)
+#[derive(Copy, Clone)]
+pub(crate) struct PixelStats {
+    pub(crate) sum: Color,
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelStats {
+    pub(crate) const fn new() -> Self {
+        Self {
+            sum: Color::ZERO,
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, c: Color) {
+        let luminance = c.dot(Color::new(0.2126, 0.7152, 0.0722));
+        self.sum += c;
+        self.count += 1;
+        let delta = luminance - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    // combines the statistics of two disjoint sets of samples (Chan et al.)
+    fn merge(&mut self, other: &Self) {
+        if other.count == 0 {
+            return;
+        }
+
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let weight = other.count as f64 / count as f64;
+
+        self.sum += other.sum;
+        self.mean += delta * weight;
+        self.m2 += (delta * delta * self.count as f64).mul_add(weight, other.m2);
+        self.count = count;
+    }
+
+    fn is_converged(&self, adaptive: &AdaptiveSampling) -> bool {
+        if self.count < adaptive.min_samples.max(2) {
+            return false;
+        }
+
+        let n = self.count as f64;
+        let variance = self.m2 / (n - 1.0);
+        let error = 1.96 * (variance / n).sqrt();
+
+        error <= adaptive.threshold * self.mean.max(1e-4)
+    }
+}
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Format {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl std::fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "checkpoint {}: {source}", path.display()),
+            Self::Format { path, message } => {
+                write!(f, "checkpoint {}: {message}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for CheckpointError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Format { .. } => None,
+        }
+    }
+}
+
+// running sums of every pixel over the passes rendered so far, the seed is the camera's and the
+// pass size and adaptive sampling are the settings', so a checkpoint is only resumed with the
+// camera and settings it was rendered with
+pub struct Accumulator {
+    pub(crate) pixels: Vec<PixelStats>,
+    width: usize,
+    height: usize,
+    seed: u64,
+    samples: usize,
+    passes: u64,
+    samples_per_pass: usize,
+    adaptive: Option<AdaptiveSampling>,
+}
+
+impl Accumulator {
+    pub fn from(width: usize, height: usize, seed: u64, settings: &ProgressiveSettings) -> Self {
+        Self {
+            pixels: vec![PixelStats::new(); width * height],
+            width,
+            height,
+            seed,
+            samples: 0,
+            passes: 0,
+            samples_per_pass: settings.samples_per_pass,
+            adaptive: settings.adaptive,
+        }
+    }
+
+    // the passes are only the same as before if they have the same size and skip the same pixels
+    pub fn matches_settings(&self, settings: &ProgressiveSettings) -> bool {
+        self.samples_per_pass == settings.samples_per_pass && self.adaptive == settings.adaptive
+    }
+
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
+    pub const fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    // samples per pixel asked for so far, converged pixels can have fewer
+    pub const fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub const fn passes(&self) -> u64 {
+        self.passes
+    }
+
+    pub fn total_samples(&self) -> u64 {
+        self.pixels.iter().map(|x| x.count).sum()
+    }
+
+    pub(crate) fn is_converged(&self, index: usize, adaptive: Option<&AdaptiveSampling>) -> bool {
+        adaptive.is_some_and(|x| self.pixels[index].is_converged(x))
+    }
+
+    pub fn converged_pixels(&self, adaptive: &AdaptiveSampling) -> usize {
+        self.pixels
+            .iter()
+            .filter(|x| x.is_converged(adaptive))
+            .count()
+    }
+
+    pub(crate) fn add_pass(&mut self, pass: Vec<PixelStats>, samples: usize) {
+        for (total, x) in self.pixels.iter_mut().zip(pass) {
+            total.merge(&x);
+        }
+        self.samples += samples;
+        self.passes += 1;
+    }
+
+    // the mean of the samples so far, black before the first pass
+    pub fn to_screen(&self) -> Screen {
+        let mut screen = Screen::from(self.width, self.height);
+        screen.screen_data = self
+            .pixels
+            .iter()
+            .map(|x| x.sum / x.count.max(1) as f64)
+            .collect();
+        screen
+    }
+
+    // the number of samples of every pixel relative to the most sampled one, for debugging
+    pub fn sample_count_screen(&self) -> Screen {
+        let mut screen = Screen::from(self.width, self.height);
+        let max = self
+            .pixels
+            .iter()
+            .map(|x| x.count)
+            .max()
+            .unwrap_or(0)
+            .max(1) as f64;
+        screen.screen_data = self
+            .pixels
+            .iter()
+            .map(|x| Color::splat(x.count as f64 / max))
+            .collect();
+        screen
+    }
+
+    // the passes only depend on their index, so the pixel statistics and the pass count are all
+    // that is needed to carry on, the file is written next to the target and renamed over it
+    pub fn save_checkpoint(&self, path: impl AsRef<Path>) -> Result<(), CheckpointError> {
+        let path = path.as_ref();
+        let io_error = |source| CheckpointError::Io {
+            path: path.to_path_buf(),
+            source,
+        };
+
+        let (has_adaptive, threshold, min_samples) = self
+            .adaptive
+            .map_or((0, 0.0, 0), |x| (1, x.threshold, x.min_samples));
+
+        let mut data = Vec::with_capacity(80 + 48 * self.pixels.len());
+        data.extend_from_slice(CHECKPOINT_MAGIC);
+        for x in [
+            self.width as u64,
+            self.height as u64,
+            self.seed,
+            self.samples as u64,
+            self.passes,
+            self.samples_per_pass as u64,
+            has_adaptive,
+            threshold.to_bits(),
+            min_samples,
+        ] {
+            data.extend_from_slice(&x.to_le_bytes());
+        }
+        for pixel in &self.pixels {
+            let [r, g, b] = pixel.sum.to_array();
+            for x in [
+                r.to_bits(),
+                g.to_bits(),
+                b.to_bits(),
+                pixel.count,
+                pixel.mean.to_bits(),
+                pixel.m2.to_bits(),
+            ] {
+                data.extend_from_slice(&x.to_le_bytes());
+            }
+        }
+
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let mut file = std::fs::File::create(&temp_path).map_err(io_error)?;
+        file.write_all(&data).map_err(io_error)?;
+        file.sync_all().map_err(io_error)?;
+        std::fs::rename(&temp_path, path).map_err(io_error)
+    }
+
+    pub fn load_checkpoint(path: impl AsRef<Path>) -> Result<Self, CheckpointError> {
+        let path = path.as_ref();
+        let format_error = |message: &str| CheckpointError::Format {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        };
+
+        let mut data = Vec::new();
+        std::fs::File::open(path)
+            .and_then(|mut x| x.read_to_end(&mut data))
+            .map_err(|source| CheckpointError::Io {
+                path: path.to_path_buf(),
+                source,
+            })?;
+
+        if !data.starts_with(CHECKPOINT_MAGIC) {
+            return Err(format_error("not a checkpoint file of this version"));
+        }
+
+        let words: Vec<u64> = data[CHECKPOINT_MAGIC.len()..]
+            .chunks_exact(8)
+            .map(|x| u64::from_le_bytes(x.try_into().unwrap()))
+            .collect();
+
+        if words.len() < CHECKPOINT_HEADER_WORDS {
+            return Err(format_error("truncated header"));
+        }
+        let [
+            width,
+            height,
+            seed,
+            samples,
+            passes,
+            samples_per_pass,
+            has_adaptive,
+            threshold,
+            min_samples,
+        ] = [0, 1, 2, 3, 4, 5, 6, 7, 8].map(|x| words[x]);
+
+        // a corrupt header must not overflow the expected size
+        let expected = usize::try_from(width)
+            .ok()
+            .zip(usize::try_from(height).ok())
+            .and_then(|(w, h)| w.checked_mul(h))
+            .and_then(|x| x.checked_mul(6))
+            .and_then(|x| x.checked_add(CHECKPOINT_HEADER_WORDS))
+            .and_then(|x| x.checked_mul(8))
+            .and_then(|x| x.checked_add(CHECKPOINT_MAGIC.len()))
+            .ok_or_else(|| format_error("the image size in the header is too large"))?;
+        if data.len() != expected {
+            return Err(format_error("the pixel data does not match the image size"));
+        }
+
+        let pixels = words[CHECKPOINT_HEADER_WORDS..]
+            .chunks_exact(6)
+            .map(|x| PixelStats {
+                sum: Color::new(
+                    f64::from_bits(x[0]),
+                    f64::from_bits(x[1]),
+                    f64::from_bits(x[2]),
+                ),
+                count: x[3],
+                mean: f64::from_bits(x[4]),
+                m2: f64::from_bits(x[5]),
+            })
+            .collect();
+
+        Ok(Self {
+            pixels,
+            width: width as usize,
+            height: height as usize,
+            seed,
+            samples: samples as usize,
+            passes,
+            samples_per_pass: samples_per_pass as usize,
+            adaptive: (has_adaptive != 0).then(|| AdaptiveSampling {
+                threshold: f64::from_bits(threshold),
+                min_samples,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::camera::{Camera, CameraConfig};
+    use crate::hittable_list::HittableList;
+    use crate::lambertian::Lambertian;
+    use crate::progressive::{AdaptiveSampling, PixelStats, ProgressiveSettings};
+    use crate::renderer::FullRenderer;
+    use crate::sphere::Sphere;
+    use crate::types::{Color, P3};
+    use crate::utility::make_prng_from;
+    use std::sync::Arc;
+
+    #[test]
+    fn check_merged_statistics_match_sequential() {
+        let mut prng = make_prng_from(9);
+        let samples: Vec<Color> = (0..100).map(|_| Color::splat(prng.gen_f64())).collect();
+
+        let mut sequential = PixelStats::new();
+        samples.iter().for_each(|x| sequential.add(*x));
+
+        let mut merged = PixelStats::new();
+        for chunk in samples.chunks(7) {
+            let mut part = PixelStats::new();
+            chunk.iter().for_each(|x| part.add(*x));
+            merged.merge(&part);
+        }
+
+        assert_eq!(merged.count, 100);
+        assert!((merged.mean - sequential.mean).abs() < 1e-12);
+        assert!((merged.m2 - sequential.m2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn check_flat_pixels_stop_early() {
+        let config = CameraConfig {
+            image_width: 16,
+            image_height: 16,
+            samples_per_pixel: 256,
+            fov: 40.0,
+            ..CameraConfig::default()
+        };
+        let camera = Camera::from_config(&config).unwrap();
+        let mat = Arc::new(Lambertian::from_color(Color::splat(0.5)));
+        let mut scene = HittableList::new();
+        scene.add(Box::new(Sphere::static_sphere(
+            P3::new(0.0, 0.0, -3.0),
+            0.5,
+            mat.clone(),
+        )));
+        scene.add(Box::new(Sphere::static_sphere(
+            P3::new(0.0, -100.5, -3.0),
+            100.0,
+            mat,
+        )));
+
+        let settings = ProgressiveSettings {
+            adaptive: Some(AdaptiveSampling::new(0.01)),
+            ..ProgressiveSettings::new(16)
+        };
+        let accumulator =
+            camera.render_progressive(&scene, &FullRenderer::new(), &settings, |_| {});
+
+        // the top corner only sees the sky and stops after the minimum, the sphere keeps sampling
+        assert_eq!(accumulator.pixels[0].count, 16);
+        assert!(accumulator.pixels[8 * 16 + 8].count > 16);
+        assert!(accumulator.total_samples() < 256 * 16 * 16);
+    }
+}
@@ -41,15 +41,8 @@ impl Quad {
         }
     }
 
-    fn set_bounding_box(q: V3, u: V3, v: V3) -> AABB {
-        let diag_1 = AABB::from_points(q, q + u + v);
-        let diag_2 = AABB::from_points(q + u, q + v);
-        AABB::from_aabbs(&diag_1, &diag_2)
-    }
-}
-
-impl Hittable for Quad {
-    fn hit(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+    // the distance and the plane coordinates of the hit
+    fn intersect(&self, r: &Ray, i: &Interval) -> Option<(f64, f64, f64)> {
         let denom = self.normal.dot(r.direction);
 
         // if we are parallel, we never hit
@@ -79,6 +72,21 @@ impl Hittable for Quad {
             return None;
         }
 
+        Some((t, alpha, beta))
+    }
+
+    fn set_bounding_box(q: V3, u: V3, v: V3) -> AABB {
+        let diag_1 = AABB::from_points(q, q + u + v);
+        let diag_2 = AABB::from_points(q + u, q + v);
+        AABB::from_aabbs(&diag_1, &diag_2)
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        let (t, alpha, beta) = self.intersect(r, i)?;
+        let p = r.at(t);
+
         let mut hr = HitRecord::from(p, self.normal, t, alpha, beta, self.mat.clone(), true);
         hr.set_face_normal(r, &self.normal);
 
@@ -89,6 +97,10 @@ impl Hittable for Quad {
         self.aabb
     }
 
+    fn occluded(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> bool {
+        self.intersect(r, i).is_some()
+    }
+
     fn pdf_value(&self, origin: &P3, dir: &V3, time: f64, prng: &mut PRNG<JsfLarge>) -> f64 {
         let hit = self.hit(
             &Ray {
@@ -104,7 +116,7 @@ impl Hittable for Quad {
             None => 0.0,
             Some(rec) => {
                 let dist_sqarted = rec.t * rec.t * dir.length_squared();
-                let cosine = rec.normal.dot(*dir) / dir.length();
+                let cosine = (rec.normal.dot(*dir) / dir.length()).abs();
 
                 dist_sqarted / (cosine * self.area)
             }
@@ -1,14 +1,58 @@
 use smolprng::{JsfLarge, PRNG};
+use crate::hit_record::HitRecord;
 use crate::hittable::Hittable;
 use crate::interval::Interval;
 use crate::material::ScatterRay;
+use crate::pdf::{MISHeuristic, PDF};
 use crate::ray::Ray;
 use crate::types::Color;
+use std::sync::Arc;
 
 pub struct SceneDetails{
     pub background: Color,
 }
 
+// the renderers that can be picked by name, e.g. from a scene file or the command line
+#[derive(Copy, Clone)]
+pub enum RendererKind {
+    Normal,
+    Full,
+    MIS(MISHeuristic),
+}
+
+impl RendererKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "normal" => Some(Self::Normal),
+            "full" => Some(Self::Full),
+            "mis" | "mis-power" => Some(Self::MIS(MISHeuristic::Power)),
+            "mis-balance" => Some(Self::MIS(MISHeuristic::Balance)),
+            _ => None,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Full => "full",
+            Self::MIS(MISHeuristic::Power) => "mis-power",
+            Self::MIS(MISHeuristic::Balance) => "mis-balance",
+        }
+    }
+
+    // without lights the MIS renderer has nothing to sample, so it falls back to the full renderer
+    pub fn build(&self, lights: Option<Arc<dyn Hittable>>) -> Box<dyn Renderer> {
+        match (self, lights) {
+            (Self::Normal, _) => Box::new(NormalRenderer {}),
+            (Self::Full, None) | (Self::MIS(_), None) => Box::new(FullRenderer::new()),
+            (Self::Full, Some(lights)) => Box::new(FullRenderer::from_lights(lights)),
+            (Self::MIS(heuristic), Some(lights)) => {
+                Box::new(MISRenderer::from(lights, *heuristic))
+            }
+        }
+    }
+}
+
 pub trait Renderer: Sync + Send {
 
     fn ray_color(
@@ -46,7 +90,139 @@ impl Renderer for NormalRenderer {
 }
 
 
-pub struct FullRenderer {}
+// path tracer, if given the emitters in the scene it will also sample them directly at every
+// diffuse bounce (next event estimation), every emitter in the scene should be in the light list
+// otherwise its contribution from diffuse bounces is lost
+pub struct FullRenderer {
+    pub lights: Option<Arc<dyn Hittable>>,
+}
+
+impl FullRenderer {
+    pub const fn new() -> Self {
+        Self { lights: None }
+    }
+
+    pub fn from_lights(lights: Arc<dyn Hittable>) -> Self {
+        Self {
+            lights: Some(lights),
+        }
+    }
+
+    fn trace(
+        &self,
+        r: &Ray,
+        depth: usize,
+        world: &dyn Hittable,
+        scene_details: &SceneDetails,
+        count_emission: bool,
+        prng: &mut PRNG<JsfLarge>,
+    ) -> Color {
+        if depth == 0 {
+            return Color::ZERO;
+        }
+
+        let hit_rec = world.hit(r, &Interval::from(0.001, f64::MAX), prng);
+
+        if hit_rec.is_none() {
+            return scene_details.background;
+        }
+
+        let rec = hit_rec.unwrap();
+
+        // emission reached by a diffuse bounce was already counted by the light sample
+        let color_from_emission = if count_emission {
+            rec.material.emitted(r, &rec, rec.u, rec.v, &rec.p)
+        } else {
+            Color::ZERO
+        };
+
+        let scatter_attempt = rec.material.scatter(r, &rec, prng);
+
+        match scatter_attempt {
+            None => color_from_emission,
+            Some(scatter) => match scatter {
+                ScatterRay::Specular {
+                    specular_ray,
+                    attenuation,
+                } => {
+                    color_from_emission
+                        + attenuation
+                            * self.trace(&specular_ray, depth - 1, world, scene_details, true, prng)
+                }
+                ScatterRay::Scatter { pdf, attenuation } => {
+                    let color_from_lights = self.lights.as_ref().map_or(Color::ZERO, |lights| {
+                        Self::sample_lights(lights, r, &rec, attenuation, world, prng)
+                    });
+
+                    let scattered = Ray::from(&rec.p, &pdf.generate(r.time, prng), r.time);
+                    let pdf_value = pdf.value(&scattered.direction, r.time, prng);
+
+                    if pdf_value <= 0.0 {
+                        return color_from_emission + color_from_lights;
+                    }
+
+                    let scattering_pdf = rec.material.scattering_pdf(r, &scattered, &rec);
+
+                    let sample_color = self.trace(
+                        &scattered,
+                        depth - 1,
+                        world,
+                        scene_details,
+                        self.lights.is_none(),
+                        prng,
+                    );
+                    let color_from_scatter =
+                        attenuation * scattering_pdf * sample_color / pdf_value;
+                    color_from_emission + color_from_lights + color_from_scatter
+                }
+            },
+        }
+    }
+
+    fn sample_lights(
+        lights: &Arc<dyn Hittable>,
+        r: &Ray,
+        rec: &HitRecord,
+        attenuation: Color,
+        world: &dyn Hittable,
+        prng: &mut PRNG<JsfLarge>,
+    ) -> Color {
+        let light_pdf = PDF::hittable(lights.clone(), &rec.p);
+        let shadow_ray = Ray::from(&rec.p, &light_pdf.generate(r.time, prng), r.time);
+        let pdf_value = light_pdf.value(&shadow_ray.direction, r.time, prng);
+
+        if pdf_value <= 0.0 {
+            return Color::ZERO;
+        }
+
+        let Some(light_rec) = lights.hit(&shadow_ray, &Interval::from(0.001, f64::MAX), prng) else {
+            return Color::ZERO;
+        };
+
+        // the light is part of the world too, so stop just short of it
+        let to_light = Interval::from(0.001, light_rec.t * (1.0 - 1e-6));
+
+        if world.occluded(&shadow_ray, &to_light, prng) {
+            return Color::ZERO;
+        }
+
+        let emitted = light_rec.material.emitted(
+            &shadow_ray,
+            &light_rec,
+            light_rec.u,
+            light_rec.v,
+            &light_rec.p,
+        );
+        let scattering_pdf = rec.material.scattering_pdf(r, &shadow_ray, rec);
+        attenuation * scattering_pdf * emitted / pdf_value
+    }
+}
+
+impl Default for FullRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Renderer for FullRenderer {
 
@@ -58,7 +234,38 @@ impl Renderer for FullRenderer {
         scene_details: &SceneDetails,
         prng: &mut PRNG<JsfLarge>,
     ) -> Color {
+        self.trace(r, depth, world, scene_details, true, prng)
+    }
+}
+
 
+// path tracer that at every diffuse bounce draws either towards the lights or from the material,
+// weighting the sample against the other strategy with a heuristic (multiple importance sampling)
+pub struct MISRenderer {
+    pub lights: Arc<dyn Hittable>,
+    pub heuristic: MISHeuristic,
+    pub light_weight: f64,
+}
+
+impl MISRenderer {
+    pub fn from(lights: Arc<dyn Hittable>, heuristic: MISHeuristic) -> Self {
+        Self {
+            lights,
+            heuristic,
+            light_weight: 0.5,
+        }
+    }
+}
+
+impl Renderer for MISRenderer {
+    fn ray_color(
+        &self,
+        r: &Ray,
+        depth: usize,
+        world: &dyn Hittable,
+        scene_details: &SceneDetails,
+        prng: &mut PRNG<JsfLarge>,
+    ) -> Color {
         if depth == 0 {
             return Color::ZERO;
         }
@@ -81,16 +288,30 @@ impl Renderer for FullRenderer {
                 ScatterRay::Specular {
                     specular_ray,
                     attenuation,
-                } => attenuation * self.ray_color(&specular_ray, depth - 1, world, scene_details, prng),
+                } => {
+                    color_from_emission
+                        + attenuation
+                            * self.ray_color(&specular_ray, depth - 1, world, scene_details, prng)
+                }
                 ScatterRay::Scatter { pdf, attenuation } => {
-                    let scattered = Ray::from(&rec.p, &pdf.generate(r.time, prng), r.time);
-                    let pdf_value = pdf.value(&scattered.direction, r.time, prng);
+                    let light_pdf = PDF::hittable(self.lights.clone(), &rec.p);
+                    let mixture = PDF::mixture(light_pdf, pdf, self.light_weight);
+
+                    let (direction, density) = mixture.generate_mis(&self.heuristic, r.time, prng);
 
-                    let sample_color = self.ray_color(&scattered, depth - 1, world, scene_details, prng);
-                    let color_from_scatter = attenuation * pdf_value * sample_color / pdf_value;
+                    if density <= 0.0 {
+                        return color_from_emission;
+                    }
+
+                    let scattered = Ray::from(&rec.p, &direction, r.time);
+                    let scattering_pdf = rec.material.scattering_pdf(r, &scattered, &rec);
+
+                    let sample_color =
+                        self.ray_color(&scattered, depth - 1, world, scene_details, prng);
+                    let color_from_scatter = attenuation * scattering_pdf * sample_color / density;
                     color_from_emission + color_from_scatter
                 }
             },
         }
     }
-}
\ No newline at end of file
+}
@@ -54,9 +54,9 @@ impl RotateY {
     }
 }
 
-impl Hittable for RotateY {
-    fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
-        // move from world space to object space
+impl RotateY {
+    // move from world space to object space
+    fn rotated_ray(&self, r: &Ray) -> Ray {
         let origin = P3::new(
             self.cos_theta
                 .mul_add(r.origin.x, -(self.sin_theta * r.origin.z)),
@@ -72,11 +72,17 @@ impl Hittable for RotateY {
                 .mul_add(r.direction.x, self.cos_theta * r.direction.z),
         );
 
-        let rotated_ray = Ray {
+        Ray {
             origin,
             direction,
             time: r.time,
-        };
+        }
+    }
+}
+
+impl Hittable for RotateY {
+    fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        let rotated_ray = self.rotated_ray(r);
 
         // determine if an intersection exits in the object space
         let hit_rec = self.object.hit(&rotated_ray, i, prng);
@@ -93,7 +99,7 @@ impl Hittable for RotateY {
                 rec.normal = V3::new(
                     self.cos_theta
                         .mul_add(rec.normal.x, self.sin_theta * rec.normal.z),
-                    r.origin.y,
+                    rec.normal.y,
                     (-self.sin_theta).mul_add(rec.normal.x, self.cos_theta * rec.normal.z),
                 );
                 Some(rec)
@@ -104,4 +110,8 @@ impl Hittable for RotateY {
     fn bounding_box(&self) -> AABB {
         self.aabb
     }
+
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        self.object.occluded(&self.rotated_ray(r), i, prng)
+    }
 }
@@ -0,0 +1,1109 @@
+use crate::animation::{Animation, CameraKeyframe};
+use crate::bvh::{BVHBuildMethod, BVHLayout, BVHNode, BVHStats};
+use crate::camera::{Camera, CameraConfig, CameraError, ShutterCurve};
+use crate::hittable::Hittable;
+use crate::hittable_list::HittableList;
+use crate::lambertian::{Dielectric, DiffuseLight, Isotropic, Lambertian, Metal};
+use crate::material::Material;
+use crate::medium::ConstantMedium;
+use crate::obj::{ObjError, ObjModel};
+use crate::perlin::Perlin;
+use crate::quad::Quad;
+use crate::ray::Ray;
+use crate::renderer::{Renderer, RendererKind};
+use crate::sphere::Sphere;
+use crate::texture::{CheckerTexture, ImageTexture, NoiseTexture, SolidColor, Texture};
+use crate::transform::{AnimatedTransform, Keyframe, Transform};
+use crate::triangle::Triangle;
+use crate::types::{Color, V3};
+use crate::utility::make_prng_from;
+use glam::DQuat;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// A scene file looks like TOML but only has what the scenes need:
+//
+//   [camera]              camera settings, keys are the fields of CameraConfig
+//   [renderer]            type = "normal" | "full" | "mis" | "mis-balance" | "mis-power"
+//   [scene]               bvh = "median" | "sah" | "none" | true | false
+//                         bvh_layout = "tree" | "linear"
+//   [texture.<name>]      type = "solid" | "checker" | "noise" | "image"
+//   [material.<name>]     type = "lambertian" | "metal" | "dielectric" | "light" | "isotropic"
+//   [animation]           frames = [first, last], fps, shutter_fraction (of a frame), the camera
+//                         then has no shutter_open or shutter_close
+//   [[camera_keyframe]]   time (seconds), look_from, look_at, fov and focus_dist
+//   [[shape]]             type = "sphere" | "quad" | "triangle" | "mesh"
+//                         scale, rotate_x, rotate_y, rotate_z (degrees) and translate place it,
+//                         the same keys ending in _end move it to a second pose at time 1
+//                         light = true | false samples it as a light, by default when its
+//                         material is of type "light"
+//   [[shape.keyframe]]    time (seconds) and the placement keys of the shape above, which they
+//                         default to, any number of them animate it instead of the _end keys
+//
+// values are numbers, "strings" without escapes, booleans or single line arrays of numbers, a
+// '#' outside a string starts a comment, and textures and materials must be defined before they
+// are referenced
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    Entry {
+        entry: String,
+        line: usize,
+        message: String,
+    },
+    Obj(ObjError),
+    Camera(CameraError),
+    Empty,
+}
+
+impl std::fmt::Display for SceneError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            Self::Syntax { line, message } => write!(f, "line {line}: {message}"),
+            Self::Entry {
+                entry,
+                line,
+                message,
+            } => write!(f, "{entry} (line {line}): {message}"),
+            Self::Obj(e) => write!(f, "{e}"),
+            Self::Camera(e) => write!(f, "camera: {e}"),
+            Self::Empty => write!(f, "the scene has no shapes"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Obj(e) => Some(e),
+            Self::Camera(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Value {
+    Number(f64),
+    Str(String),
+    Bool(bool),
+    Array(Vec<f64>),
+}
+
+impl Value {
+    const fn kind(&self) -> &'static str {
+        match self {
+            Self::Number(_) => "a number",
+            Self::Str(_) => "a string",
+            Self::Bool(_) => "a boolean",
+            Self::Array(_) => "an array",
+        }
+    }
+}
+
+// one [table] or [[array]] entry of the file, a shape holds its [[shape.keyframe]] entries
+struct Table {
+    name: String,
+    line: usize,
+    values: Vec<(String, Value, usize)>,
+    keyframes: Vec<Self>,
+}
+
+impl Table {
+    fn error(&self, message: String) -> SceneError {
+        SceneError::Entry {
+            entry: self.name.clone(),
+            line: self.line,
+            message,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.values.iter().find(|x| x.0 == key).map(|x| &x.1)
+    }
+
+    fn check_keys(&self, allowed: &[&str]) -> Result<(), SceneError> {
+        for (key, _, line) in &self.values {
+            if !allowed.contains(&key.as_str()) {
+                return Err(self.error(format!("unknown key `{key}` on line {line}")));
+            }
+        }
+        Ok(())
+    }
+
+    fn wrong_type(&self, key: &str, expected: &str, found: &Value) -> SceneError {
+        self.error(format!(
+            "`{key}` should be {expected}, found {}",
+            found.kind()
+        ))
+    }
+
+    fn number_or(&self, key: &str, default: f64) -> Result<f64, SceneError> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(Value::Number(x)) => Ok(*x),
+            Some(other) => Err(self.wrong_type(key, "a number", other)),
+        }
+    }
+
+    fn number(&self, key: &str) -> Result<f64, SceneError> {
+        self.require(key)?;
+        self.number_or(key, 0.0)
+    }
+
+    fn count_or(&self, key: &str, default: usize) -> Result<usize, SceneError> {
+        let x = self.number_or(key, default as f64)?;
+        if x < 0.0 || x.fract() != 0.0 {
+            return Err(self.error(format!("`{key}` should be a whole number, found {x}")));
+        }
+        Ok(x as usize)
+    }
+
+    fn vec3_or(&self, key: &str, default: V3) -> Result<V3, SceneError> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(Value::Array(x)) if x.len() == 3 => Ok(V3::new(x[0], x[1], x[2])),
+            Some(Value::Number(x)) => Ok(V3::splat(*x)),
+            Some(other) => Err(self.wrong_type(key, "an array of 3 numbers", other)),
+        }
+    }
+
+    fn vec3(&self, key: &str) -> Result<V3, SceneError> {
+        self.require(key)?;
+        self.vec3_or(key, V3::ZERO)
+    }
+
+    fn string(&self, key: &str) -> Result<&str, SceneError> {
+        match self.get(key) {
+            None => Err(self.error(format!("missing `{key}`"))),
+            Some(Value::Str(x)) => Ok(x),
+            Some(other) => Err(self.wrong_type(key, "a string", other)),
+        }
+    }
+
+    fn bool_or(&self, key: &str, default: bool) -> Result<bool, SceneError> {
+        match self.get(key) {
+            None => Ok(default),
+            Some(Value::Bool(x)) => Ok(*x),
+            Some(other) => Err(self.wrong_type(key, "a boolean", other)),
+        }
+    }
+
+    fn require(&self, key: &str) -> Result<(), SceneError> {
+        match self.get(key) {
+            None => Err(self.error(format!("missing `{key}`"))),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+fn parse_value(text: &str) -> Result<Value, String> {
+    if let Some(inner) = text.strip_prefix('"') {
+        return inner
+            .strip_suffix('"')
+            .map(|x| Value::Str(x.to_string()))
+            .ok_or_else(|| format!("unterminated string `{text}`"));
+    }
+
+    if let Some(inner) = text.strip_prefix('[') {
+        let inner = inner
+            .strip_suffix(']')
+            .ok_or_else(|| format!("unterminated array `{text}`"))?;
+        return inner
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|x| {
+                x.parse::<f64>()
+                    .map_err(|_| format!("`{x}` is not a number"))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array);
+    }
+
+    match text {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => text
+            .parse()
+            .map(Value::Number)
+            .map_err(|_| format!("cannot read value `{text}`")),
+    }
+}
+
+// everything after a '#' that is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+fn parse_tables(source: &str) -> Result<Vec<Table>, SceneError> {
+    let mut tables: Vec<Table> = Vec::new();
+    let mut array_count = 0;
+
+    for (line_number, line) in source.lines().enumerate() {
+        let line_number = line_number + 1;
+        let syntax = |message: String| SceneError::Syntax {
+            line: line_number,
+            message,
+        };
+
+        let line = strip_comment(line).trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("[[") {
+            let name = name
+                .strip_suffix("]]")
+                .ok_or_else(|| syntax(format!("malformed header `{line}`")))?
+                .trim();
+            if !["shape", "shape.keyframe", "camera_keyframe"].contains(&name) {
+                return Err(syntax(format!("unknown array of tables `{name}`")));
+            }
+            if name == "shape.keyframe"
+                && !tables.last().is_some_and(|x| x.name.starts_with("shape"))
+            {
+                return Err(syntax(
+                    "a keyframe must follow the [[shape]] it moves".to_string(),
+                ));
+            }
+            array_count += 1;
+            tables.push(Table {
+                name: format!("{name} #{array_count}"),
+                line: line_number,
+                values: Vec::new(),
+                keyframes: Vec::new(),
+            });
+        } else if let Some(name) = line.strip_prefix('[') {
+            let name = name
+                .strip_suffix(']')
+                .ok_or_else(|| syntax(format!("malformed header `{line}`")))?
+                .trim();
+            if tables.iter().any(|x| x.name == name) {
+                return Err(syntax(format!("`{name}` is defined twice")));
+            }
+            tables.push(Table {
+                name: name.to_string(),
+                line: line_number,
+                values: Vec::new(),
+                keyframes: Vec::new(),
+            });
+        } else {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| syntax(format!("expected `key = value`, found `{line}`")))?;
+            let key = key.trim();
+            let value = parse_value(value.trim()).map_err(syntax)?;
+
+            let table = tables
+                .last_mut()
+                .ok_or_else(|| syntax(format!("`{key}` is not inside a table")))?;
+
+            if table.values.iter().any(|x| x.0 == key) {
+                return Err(syntax(format!("`{key}` is set twice in `{}`", table.name)));
+            }
+            table.values.push((key.to_string(), value, line_number));
+        }
+    }
+
+    // the keyframes go to the shape they follow
+    let mut nested: Vec<Table> = Vec::new();
+    for table in tables {
+        match nested.last_mut() {
+            Some(shape) if table.name.starts_with("shape.keyframe #") => {
+                shape.keyframes.push(table)
+            }
+            _ => nested.push(table),
+        }
+    }
+
+    Ok(nested)
+}
+
+// rotations are split into steps of at most this many degrees about each axis, so the
+// quaternions between them turn less than half a turn and go the way the angles do
+const MAX_ROTATION_STEP: f64 = 45.0;
+
+// the placement of a shape at one point in time, keeping the angles so a rotation of any size
+// can be interpolated
+#[derive(Copy, Clone)]
+struct Pose {
+    time: f64,
+    scale: V3,
+    angles: V3,
+    translation: V3,
+}
+
+impl Pose {
+    const fn new(time: f64) -> Self {
+        Self {
+            time,
+            scale: V3::ONE,
+            angles: V3::ZERO,
+            translation: V3::ZERO,
+        }
+    }
+
+    fn lerp(&self, other: &Self, s: f64) -> Self {
+        Self {
+            time: (other.time - self.time).mul_add(s, self.time),
+            scale: self.scale.lerp(other.scale, s),
+            angles: self.angles.lerp(other.angles, s),
+            translation: self.translation.lerp(other.translation, s),
+        }
+    }
+
+    // scaled first, then rotated about x, y and z in that order, then translated
+    fn keyframe(&self) -> Keyframe {
+        let radians = self.angles.map(f64::to_radians);
+        Keyframe {
+            translation: self.translation,
+            rotation: DQuat::from_rotation_z(radians.z)
+                * DQuat::from_rotation_y(radians.y)
+                * DQuat::from_rotation_x(radians.x),
+            scale: self.scale,
+            ..Keyframe::new(self.time)
+        }
+    }
+}
+
+// the keyframes through the poses, with extra ones wherever a rotation is too large for slerp
+fn keyframes(mut poses: Vec<Pose>) -> Vec<Keyframe> {
+    poses.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    let mut keyframes = Vec::new();
+    for pair in poses.windows(2) {
+        let turn = (pair[1].angles - pair[0].angles).abs().max_element();
+        let steps = (turn / MAX_ROTATION_STEP).ceil().max(1.0) as usize;
+        for k in 0..steps {
+            keyframes.push(pair[0].lerp(&pair[1], k as f64 / steps as f64).keyframe());
+        }
+    }
+    keyframes.extend(poses.last().map(Pose::keyframe));
+
+    keyframes
+}
+
+pub struct Scene {
+    pub camera: CameraConfig,
+    pub renderer: RendererKind,
+    pub world: Arc<dyn Hittable>,
+    pub lights: Option<Arc<dyn Hittable>>,
+    pub primitive_count: usize,
+    pub bvh_stats: Option<BVHStats>,
+    pub animation: Option<Animation>,
+}
+
+impl Scene {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SceneError> {
+        Self::load_with(path, None, None)
+    }
+
+    // same as load, but the bvh build method and layout override the ones in the file when given
+    pub fn load_with(
+        path: impl AsRef<Path>,
+        bvh: Option<BVHBuildMethod>,
+        layout: Option<BVHLayout>,
+    ) -> Result<Self, SceneError> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|source| SceneError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        let directory = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Self::parse_with(&source, &directory, bvh, layout)
+    }
+
+    // relative file names in the scene are resolved against `directory`
+    pub fn parse(source: &str, directory: &Path) -> Result<Self, SceneError> {
+        Self::parse_with(source, directory, None, None)
+    }
+
+    pub fn parse_with(
+        source: &str,
+        directory: &Path,
+        bvh: Option<BVHBuildMethod>,
+        layout: Option<BVHLayout>,
+    ) -> Result<Self, SceneError> {
+        let tables = parse_tables(source)?;
+        let mut builder = SceneBuilder {
+            directory: directory.to_path_buf(),
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            emissive: HashSet::new(),
+            world: HittableList::new(),
+            lights: HittableList::new(),
+            primitive_count: 0,
+        };
+
+        let mut camera = CameraConfig::default();
+        let mut renderer = RendererKind::Full;
+        let mut bvh_method = Some(BVHBuildMethod::Median);
+        let mut bvh_layout = BVHLayout::Tree;
+        let mut animation: Option<Animation> = None;
+        let mut camera_keyframes = Vec::new();
+
+        for table in &tables {
+            match table.name.split_once('.') {
+                Some(("texture", name)) => {
+                    let tex = builder.texture(table)?;
+                    builder.textures.insert(name.to_string(), tex);
+                }
+                Some(("material", name)) => {
+                    let mat = builder.material(table)?;
+                    builder.materials.insert(name.to_string(), mat);
+                    if table.string("type")? == "light" {
+                        builder.emissive.insert(name.to_string());
+                    }
+                }
+                _ => match table.name.as_str() {
+                    "camera" => camera = Self::camera(table)?,
+                    "renderer" => {
+                        table.check_keys(&["type"])?;
+                        let name = table.string("type")?;
+                        renderer = RendererKind::from_name(name)
+                            .ok_or_else(|| table.error(format!("unknown renderer `{name}`")))?;
+                    }
+                    "scene" => {
+                        table.check_keys(&["bvh", "bvh_layout"])?;
+                        bvh_method = Self::bvh_method(table)?;
+                        if table.get("bvh_layout").is_some() {
+                            let name = table.string("bvh_layout")?;
+                            bvh_layout = BVHLayout::from_name(name).ok_or_else(|| {
+                                table.error(format!("unknown bvh layout `{name}`"))
+                            })?;
+                        }
+                    }
+                    "animation" => animation = Some(Self::animation(table)?),
+                    name if name.starts_with("shape #") => builder.shape(table)?,
+                    name if name.starts_with("camera_keyframe #") => camera_keyframes.push(table),
+                    name => return Err(table.error(format!("unknown table `{name}`"))),
+                },
+            }
+        }
+
+        camera.validate().map_err(SceneError::Camera)?;
+
+        // every frame opens and closes the shutter itself
+        if animation.is_some()
+            && let Some(table) = tables.iter().find(|x| x.name == "camera")
+            && let Some(key) = ["shutter_open", "shutter_close"]
+                .into_iter()
+                .find(|x| table.get(x).is_some())
+        {
+            return Err(table.error(format!(
+                "`{key}` is set by the animation, use `shutter_fraction` in [animation]"
+            )));
+        }
+
+        // the keyframes fall back to the camera table, which can come after them
+        if let Some(table) = camera_keyframes.first()
+            && animation.is_none()
+        {
+            return Err(table.error("camera keyframes need an [animation] table".to_string()));
+        }
+        if let Some(animation) = &mut animation {
+            for table in camera_keyframes {
+                animation
+                    .camera_keyframes
+                    .push(Self::camera_keyframe(table, &camera)?);
+            }
+        }
+
+        if builder.world.objects.is_empty() {
+            return Err(SceneError::Empty);
+        }
+
+        let (world, bvh_stats): (Arc<dyn Hittable>, _) = match bvh.or(bvh_method) {
+            Some(method) => {
+                let layout = layout.unwrap_or(bvh_layout);
+                let (bvh, stats) = layout.build(&mut builder.world.objects, method);
+                (bvh, Some(stats))
+            }
+            None => (Arc::new(builder.world), None),
+        };
+
+        let lights: Option<Arc<dyn Hittable>> = if builder.lights.objects.is_empty() {
+            None
+        } else {
+            Some(Arc::new(builder.lights))
+        };
+
+        Ok(Self {
+            camera,
+            renderer,
+            world,
+            lights,
+            primitive_count: builder.primitive_count,
+            bvh_stats,
+            animation,
+        })
+    }
+
+    fn bvh_method(table: &Table) -> Result<Option<BVHBuildMethod>, SceneError> {
+        match table.get("bvh") {
+            None | Some(Value::Bool(true)) => Ok(Some(BVHBuildMethod::Median)),
+            Some(Value::Bool(false)) => Ok(None),
+            Some(Value::Str(x)) if x == "none" => Ok(None),
+            Some(Value::Str(x)) => BVHBuildMethod::from_name(x)
+                .map(Some)
+                .ok_or_else(|| table.error(format!("unknown bvh build method `{x}`"))),
+            Some(other) => Err(table.wrong_type("bvh", "a string or a boolean", other)),
+        }
+    }
+
+    fn animation(table: &Table) -> Result<Animation, SceneError> {
+        table.check_keys(&["frames", "fps", "shutter_fraction"])?;
+
+        let frames = match table.get("frames") {
+            Some(Value::Array(x))
+                if x.len() == 2 && x.iter().all(|x| *x >= 0.0 && x.fract() == 0.0) =>
+            {
+                x[0] as usize..=x[1] as usize
+            }
+            Some(other) => {
+                return Err(table.wrong_type("frames", "the first and last frame", other));
+            }
+            None => return Err(table.error("missing `frames`".to_string())),
+        };
+
+        let default = Animation::from(frames, 24.0);
+        let animation = Animation {
+            fps: table.number_or("fps", default.fps)?,
+            shutter_fraction: table.number_or("shutter_fraction", default.shutter_fraction)?,
+            ..default
+        };
+        animation
+            .validate()
+            .map_err(|e| table.error(e.to_string()))?;
+
+        Ok(animation)
+    }
+
+    fn camera_keyframe(table: &Table, camera: &CameraConfig) -> Result<CameraKeyframe, SceneError> {
+        table.check_keys(&["time", "look_from", "look_at", "fov", "focus_dist"])?;
+
+        Ok(CameraKeyframe {
+            look_from: table.vec3_or("look_from", camera.look_from)?,
+            look_at: table.vec3_or("look_at", camera.look_at)?,
+            fov: table.number_or("fov", camera.fov)?,
+            focus_dist: table.number_or("focus_dist", camera.focus_dist)?,
+            ..CameraKeyframe::from_config(table.number("time")?, camera)
+        })
+    }
+
+    pub fn make_camera(&self) -> Result<Camera, CameraError> {
+        Camera::from_config(&self.camera)
+    }
+
+    pub fn make_renderer(&self) -> Box<dyn Renderer> {
+        self.renderer.build(self.lights.clone())
+    }
+
+    fn camera(table: &Table) -> Result<CameraConfig, SceneError> {
+        table.check_keys(&[
+            "image_width",
+            "image_height",
+            "samples_per_pixel",
+            "max_depth",
+            "fov",
+            "look_from",
+            "look_at",
+            "v_up",
+            "defocus_angle",
+            "focus_dist",
+            "background",
+            "seed",
+            "shutter_open",
+            "shutter_close",
+            "shutter",
+            "rolling_exposure",
+            "motion_blur",
+        ])?;
+
+        let default = CameraConfig::default();
+
+        let mut shutter_curve = if table.get("shutter").is_some() {
+            let name = table.string("shutter")?;
+            ShutterCurve::from_name(name)
+                .ok_or_else(|| table.error(format!("unknown shutter `{name}`")))?
+        } else {
+            default.shutter_curve
+        };
+        if let ShutterCurve::Rolling { exposure } = &mut shutter_curve {
+            *exposure = table.number_or("rolling_exposure", *exposure)?;
+        } else if table.get("rolling_exposure").is_some() {
+            return Err(table.error("`rolling_exposure` needs shutter = \"rolling\"".into()));
+        }
+
+        Ok(CameraConfig {
+            image_width: table.count_or("image_width", default.image_width)?,
+            image_height: table.count_or("image_height", default.image_height)?,
+            samples_per_pixel: table.count_or("samples_per_pixel", default.samples_per_pixel)?,
+            max_depth: table.count_or("max_depth", default.max_depth)?,
+            fov: table.number_or("fov", default.fov)?,
+            look_from: table.vec3_or("look_from", default.look_from)?,
+            look_at: table.vec3_or("look_at", default.look_at)?,
+            v_up: table.vec3_or("v_up", default.v_up)?,
+            defocus_angle: table.number_or("defocus_angle", default.defocus_angle)?,
+            focus_dist: table.number_or("focus_dist", default.focus_dist)?,
+            background: table.vec3_or("background", default.background)?,
+            seed: table.count_or("seed", default.seed as usize)? as u64,
+            shutter_open: table.number_or("shutter_open", default.shutter_open)?,
+            shutter_close: table.number_or("shutter_close", default.shutter_close)?,
+            shutter_curve,
+            motion_blur: table.bool_or("motion_blur", default.motion_blur)?,
+        })
+    }
+}
+
+struct SceneBuilder {
+    directory: PathBuf,
+    textures: HashMap<String, Arc<dyn Texture>>,
+    materials: HashMap<String, Arc<dyn Material>>,
+    // the materials of type "light"
+    emissive: HashSet<String>,
+    world: HittableList,
+    lights: HittableList,
+    primitive_count: usize,
+}
+
+impl SceneBuilder {
+    fn lookup_texture(&self, table: &Table, key: &str) -> Result<Arc<dyn Texture>, SceneError> {
+        let name = table.string(key)?;
+        self.textures
+            .get(name)
+            .cloned()
+            .ok_or_else(|| table.error(format!("texture `{name}` is not defined before use")))
+    }
+
+    // materials take either a `texture` or a `color`
+    fn texture_or_color(&self, table: &Table) -> Result<Arc<dyn Texture>, SceneError> {
+        if table.get("texture").is_some() {
+            self.lookup_texture(table, "texture")
+        } else {
+            Ok(Arc::new(SolidColor {
+                albedo: table.vec3_or("color", Color::splat(0.5))?,
+            }))
+        }
+    }
+
+    fn texture(&self, table: &Table) -> Result<Arc<dyn Texture>, SceneError> {
+        let kind = table.string("type")?;
+
+        let tex: Arc<dyn Texture> = match kind {
+            "solid" => {
+                table.check_keys(&["type", "color"])?;
+                Arc::new(SolidColor {
+                    albedo: table.vec3("color")?,
+                })
+            }
+            "checker" => {
+                table.check_keys(&["type", "scale", "even", "odd"])?;
+                Arc::new(CheckerTexture {
+                    inv_scale: 1.0 / table.number_or("scale", 1.0)?,
+                    even: self.lookup_texture(table, "even")?,
+                    odd: self.lookup_texture(table, "odd")?,
+                })
+            }
+            "noise" => {
+                table.check_keys(&["type", "scale", "seed"])?;
+                let mut prng = make_prng_from(table.count_or("seed", 0)? as u64);
+                Arc::new(NoiseTexture::from(
+                    Perlin::new(&mut prng),
+                    table.number_or("scale", 1.0)?,
+                ))
+            }
+            "image" => {
+                table.check_keys(&["type", "file"])?;
+                let path = self.directory.join(table.string("file")?);
+                if !path.is_file() {
+                    return Err(table.error(format!("image `{}` does not exist", path.display())));
+                }
+                Arc::new(ImageTexture::new(&path.to_string_lossy()))
+            }
+            _ => return Err(table.error(format!("unknown texture type `{kind}`"))),
+        };
+
+        Ok(tex)
+    }
+
+    fn material(&self, table: &Table) -> Result<Arc<dyn Material>, SceneError> {
+        let kind = table.string("type")?;
+
+        let mat: Arc<dyn Material> = match kind {
+            "lambertian" => {
+                table.check_keys(&["type", "color", "texture"])?;
+                Arc::new(Lambertian::from_texture(self.texture_or_color(table)?))
+            }
+            "metal" => {
+                table.check_keys(&["type", "color", "fuzz"])?;
+                Arc::new(Metal {
+                    albedo: table.vec3("color")?,
+                    fuzz: table.number_or("fuzz", 0.0)?,
+                })
+            }
+            "dielectric" => {
+                table.check_keys(&["type", "refraction_index"])?;
+                Arc::new(Dielectric {
+                    refraction_index: table.number("refraction_index")?,
+                })
+            }
+            "light" => {
+                table.check_keys(&["type", "color", "texture"])?;
+                Arc::new(DiffuseLight::from_texture(self.texture_or_color(table)?))
+            }
+            "isotropic" => {
+                table.check_keys(&["type", "color", "texture"])?;
+                Arc::new(Isotropic::from_texture(self.texture_or_color(table)?))
+            }
+            _ => return Err(table.error(format!("unknown material type `{kind}`"))),
+        };
+
+        Ok(mat)
+    }
+
+    // the placement keys ending in `suffix`, the ones that are not set are taken from `base`
+    fn pose(table: &Table, suffix: &str, time: f64, base: &Pose) -> Result<Pose, SceneError> {
+        let vec3 = |key: &str, default| table.vec3_or(&format!("{key}{suffix}"), default);
+        let angle = |key: &str, default| table.number_or(&format!("{key}{suffix}"), default);
+
+        let scale = vec3("scale", base.scale)?;
+        if scale.cmpeq(V3::ZERO).any() {
+            return Err(table.error(format!("`scale{suffix}` cannot be zero, found {scale}")));
+        }
+
+        Ok(Pose {
+            time,
+            scale,
+            angles: V3::new(
+                angle("rotate_x", base.angles.x)?,
+                angle("rotate_y", base.angles.y)?,
+                angle("rotate_z", base.angles.z)?,
+            ),
+            translation: vec3("translate", base.translation)?,
+        })
+    }
+
+    // the `_end` keys give a second pose at time 1, [[shape.keyframe]] entries any number of
+    // poses, both default to the shape's own keys
+    fn place(table: &Table, object: Arc<dyn Hittable>) -> Result<Arc<dyn Hittable>, SceneError> {
+        let keys = ["scale", "rotate_x", "rotate_y", "rotate_z", "translate"];
+        let is_set = |suffix: &str| {
+            keys.iter()
+                .any(|x| table.get(&format!("{x}{suffix}")).is_some())
+        };
+
+        let start = Self::pose(table, "", 0.0, &Pose::new(0.0))?;
+
+        if !table.keyframes.is_empty() {
+            if is_set("_end") {
+                return Err(table.error("use either the `_end` keys or keyframes".to_string()));
+            }
+            let mut poses = Vec::new();
+            for keyframe in &table.keyframes {
+                keyframe.check_keys(&[&["time"], &keys[..]].concat())?;
+                let time = keyframe.number("time")?;
+                poses.push(Self::pose(keyframe, "", time, &start)?);
+            }
+            return Ok(Arc::new(AnimatedTransform::from(object, keyframes(poses))));
+        }
+
+        if is_set("_end") {
+            let end = Self::pose(table, "_end", 1.0, &start)?;
+            return Ok(Arc::new(AnimatedTransform::from(
+                object,
+                keyframes(vec![start, end]),
+            )));
+        }
+
+        if is_set("") {
+            let matrix = start.keyframe().matrix();
+            return Ok(Arc::new(Transform::from(object, matrix)));
+        }
+
+        Ok(object)
+    }
+
+    fn shape(&mut self, table: &Table) -> Result<(), SceneError> {
+        let kind = table.string("type")?;
+
+        // keys every shape understands, `color` and `texture` are the albedo of a medium
+        let common = [
+            "type",
+            "light",
+            "scale",
+            "rotate_x",
+            "rotate_y",
+            "rotate_z",
+            "translate",
+            "scale_end",
+            "rotate_x_end",
+            "rotate_y_end",
+            "rotate_z_end",
+            "translate_end",
+            "density",
+            "color",
+            "texture",
+        ];
+        let keys = |extra: &[&'static str]| [&common[..], extra].concat();
+
+        let is_medium = table.get("density").is_some();
+
+        if !is_medium && (table.get("color").is_some() || table.get("texture").is_some()) {
+            return Err(table.error("`color` and `texture` are only used with `density`".into()));
+        }
+
+        // the boundary of a medium is never shaded, so it does not need a material
+        let mat = || -> Result<Arc<dyn Material>, SceneError> {
+            if is_medium && table.get("material").is_none() {
+                return Ok(Arc::new(Lambertian::from_color(Color::splat(0.5))));
+            }
+            let name = table.string("material")?;
+            self.materials
+                .get(name)
+                .cloned()
+                .ok_or_else(|| table.error(format!("material `{name}` is not defined before use")))
+        };
+
+        // meshes also return their emissive groups, which are sampled as lights on their own
+        let (object, emissive): (Arc<dyn Hittable>, Option<Arc<dyn Hittable>>) = match kind {
+            "sphere" => {
+                table.check_keys(&keys(&["material", "center", "center_end", "radius"]))?;
+                let center = table.vec3("center")?;
+                let center_end = table.vec3_or("center_end", center)?;
+                let sphere = Sphere::from(
+                    Ray::from(&center, &(center_end - center), 0.0),
+                    table.number("radius")?,
+                    mat()?,
+                );
+                self.primitive_count += 1;
+                (Arc::new(sphere), None)
+            }
+            "quad" => {
+                table.check_keys(&keys(&["material", "q", "u", "v"]))?;
+                let quad = Quad::new(table.vec3("q")?, table.vec3("u")?, table.vec3("v")?, mat()?);
+                self.primitive_count += 1;
+                (Arc::new(quad), None)
+            }
+            "triangle" => {
+                table.check_keys(&keys(&["material", "a", "b", "c"]))?;
+                let triangle =
+                    Triangle::new(table.vec3("a")?, table.vec3("b")?, table.vec3("c")?, mat()?);
+                self.primitive_count += 1;
+                (Arc::new(triangle), None)
+            }
+            "mesh" => {
+                table.check_keys(&keys(&["file"]))?;
+                let path = self.directory.join(table.string("file")?);
+                let model = ObjModel::load(path).map_err(SceneError::Obj)?;
+                self.primitive_count += model.triangle_count();
+                let (mut groups, lights) = model.into_lists();
+                let mesh: Arc<dyn Hittable> = if groups.objects.len() > 1 {
+                    Arc::new(BVHNode::from(&mut groups.objects))
+                } else {
+                    Arc::new(groups)
+                };
+                let lights: Option<Arc<dyn Hittable>> = if lights.objects.is_empty() {
+                    None
+                } else {
+                    Some(Arc::new(lights))
+                };
+                (mesh, lights)
+            }
+            _ => return Err(table.error(format!("unknown shape type `{kind}`"))),
+        };
+
+        // the renderers leave emission after a diffuse bounce to the light sampling, so shapes
+        // that emit are sampled unless `light = false`, only surfaces can be sampled, a medium
+        // has no density to draw directions from
+        let emits = !is_medium
+            && matches!(table.get("material"), Some(Value::Str(x)) if self.emissive.contains(x));
+        let is_light = table.bool_or("light", emits)?;
+        if is_light && is_medium {
+            return Err(table.error("a medium cannot be a light".to_string()));
+        }
+
+        let mut object = Self::place(table, object)?;
+
+        // the boundary of a medium is never shaded, so its emissive groups do not light anything
+        let emissive = match emissive {
+            Some(lights) if !is_light && !is_medium => Some(Self::place(table, lights)?),
+            _ => None,
+        };
+
+        if is_medium {
+            let density = table.number("density")?;
+            if density <= 0.0 {
+                return Err(table.error(format!("`density` must be positive, found {density}")));
+            }
+            let tex = self.texture_or_color(table)?;
+            object = Arc::new(ConstantMedium::from(object, density, tex));
+        }
+
+        if is_light {
+            self.lights.add(Box::new(object.clone()));
+        }
+        if let Some(lights) = emissive {
+            self.lights.add(Box::new(lights));
+        }
+
+        self.world.add(Box::new(object));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::renderer::RendererKind;
+    use crate::scene::{Pose, Scene, SceneError, keyframes};
+    use crate::types::V3;
+    use std::path::Path;
+
+    #[test]
+    fn check_example_scene_and_errors() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenes/simple_light.scene");
+        let scene = Scene::load(path).unwrap();
+        assert_eq!(scene.primitive_count, 3);
+        assert!(scene.lights.is_some());
+        assert!(scene.make_camera().is_ok());
+
+        let source = "[material.red]\ntype = \"lambertian\"\ncolor = [1, 0, 0]\n\n\
+                      [[shape]]\ntype = \"sphere\"\ncenter = [0, 0, 0]\nradius = 1\n\
+                      material = \"blue\"\n";
+        let error = Scene::parse(source, Path::new("."));
+        assert!(matches!(error, Err(SceneError::Entry { line: 5, .. })));
+
+        let error = Scene::parse("[camera]\nfov = [1, 2\n", Path::new("."));
+        assert!(matches!(error, Err(SceneError::Syntax { line: 2, .. })));
+
+        let error = Scene::parse("[camera] # no shapes\nfov = 30\n", Path::new("."));
+        assert!(matches!(error, Err(SceneError::Empty)));
+
+        // a '#' inside a string is part of it
+        let error = Scene::parse(&source.replace("\"blue\"", "\"blue #2\""), Path::new("."));
+        assert!(
+            matches!(error, Err(SceneError::Entry { message, .. }) if message.contains("`blue #2`"))
+        );
+
+        let source = "[material.lamp]\ntype = \"light\"\ncolor = [4, 4, 4]\n\n\
+                      [[shape]]\ntype = \"quad\"\nq = [0, 0, 0]\nu = [1, 0, 0]\nv = [0, 1, 0]\n\
+                      material = \"lamp\"\nlight = true\nrotate_x = 90\nscale = [2, 1, 1]\n";
+        let scene = Scene::parse(source, Path::new(".")).unwrap();
+        assert!(scene.lights.is_some());
+
+        // a shape with a light material is sampled without being told to
+        let implicit = Scene::parse(&source.replace("light = true\n", ""), Path::new("."));
+        assert!(implicit.unwrap().lights.is_some());
+        let unsampled = Scene::parse(
+            &source.replace("light = true", "light = false"),
+            Path::new("."),
+        );
+        assert!(unsampled.unwrap().lights.is_none());
+
+        let moving = Scene::parse(&source.replace("scale", "scale_end"), Path::new("."));
+        assert!(moving.is_ok());
+
+        let animated = format!(
+            "[[camera_keyframe]]\ntime = 1\nfov = 60\n\n[animation]\nframes = [0, 10]\nfps = 12\n\n\
+             [camera]\nfov = 30\n\n{source}"
+        );
+        let animation = Scene::parse(&animated, Path::new("."))
+            .unwrap()
+            .animation
+            .unwrap();
+        assert_eq!(animation.frames, 0..=10);
+        assert_eq!(animation.camera_keyframes[0].fov, 60.0);
+        assert_eq!(animation.camera_keyframes[0].focus_dist, 10.0);
+        let backwards = Scene::parse(&animated.replace("[0, 10]", "[10, 0]"), Path::new("."));
+        assert!(matches!(backwards, Err(SceneError::Entry { .. })));
+        let shutter = animated.replace("fov = 30", "fov = 30\nshutter_close = 0.5");
+        let shutter = Scene::parse(&shutter, Path::new("."));
+        assert!(matches!(shutter, Err(SceneError::Entry { line: 9, .. })));
+        let error = Scene::parse(&source.replace("[2, 1, 1]", "[2, 0, 1]"), Path::new("."));
+        assert!(matches!(error, Err(SceneError::Entry { line: 5, .. })));
+        let error = Scene::parse(&format!("{source}density = 0.5\n"), Path::new("."));
+        assert!(matches!(error, Err(SceneError::Entry { line: 5, .. })));
+    }
+
+    #[test]
+    fn check_shape_keyframes_turn_the_whole_way() {
+        let source = "[material.grey]\ntype = \"lambertian\"\ncolor = [0.5, 0.5, 0.5]\n\n\
+                      [[shape]]\ntype = \"sphere\"\ncenter = [1, 0, 0]\nradius = 0.5\n\
+                      material = \"grey\"\ntranslate = [0, 1, 0]\n\n\
+                      [[shape.keyframe]]\ntime = 0\n\n\
+                      [[shape.keyframe]]\ntime = 2\nrotate_y = 360\n";
+        assert!(Scene::parse(source, Path::new(".")).is_ok());
+
+        let error = Scene::parse(&source.replace("time = 2", "turn = 2"), Path::new("."));
+        assert!(matches!(error, Err(SceneError::Entry { line: 15, .. })));
+        let error = Scene::parse(&source[source.find("[[shape.").unwrap()..], Path::new("."));
+        assert!(matches!(error, Err(SceneError::Syntax { line: 1, .. })));
+
+        // a full turn is split so that half way the sphere is on the other side
+        let keyframes = keyframes(vec![
+            Pose::new(0.0),
+            Pose {
+                angles: V3::new(0.0, 360.0, 0.0),
+                ..Pose::new(2.0)
+            },
+        ]);
+        assert_eq!(keyframes.len(), 9);
+        assert_eq!(keyframes[4].time, 1.0);
+        assert!((keyframes[4].matrix().transform_point3(V3::X) + V3::X).length() < 1e-9);
+    }
+
+    #[test]
+    fn check_emissive_mesh_groups_light_the_scene() {
+        let dir = std::env::temp_dir().join(format!("spt_scene_mesh_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // a white floor and a small lamp facing down at it, in one obj file
+        let mtl = "newmtl white\nKd 0.8\nnewmtl lamp\nKe 8\n";
+        let obj = "mtllib room.mtl\n\
+                   v -2 0 -2\nv -2 0 2\nv 2 0 2\nv 2 0 -2\n\
+                   v -0.5 2 -0.5\nv 0.5 2 -0.5\nv 0.5 2 0.5\nv -0.5 2 0.5\n\
+                   g floor\nusemtl white\nf 1 2 3 4\ng lamp\nusemtl lamp\nf 5 6 7 8\n";
+        std::fs::write(dir.join("room.mtl"), mtl).unwrap();
+        std::fs::write(dir.join("room.obj"), obj).unwrap();
+
+        let source = "[camera]\nimage_width = 8\nimage_height = 8\nsamples_per_pixel = 16\n\
+                      look_from = [0, 5, 5]\nlook_at = [0, 0, 0]\nfov = 30\n\
+                      background = [0, 0, 0]\n\n\
+                      [[shape]]\ntype = \"mesh\"\nfile = \"room.obj\"\n";
+        let scene = Scene::parse(source, &dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let scene = scene.unwrap();
+        assert!(matches!(scene.renderer, RendererKind::Full));
+        assert!(scene.lights.is_some());
+
+        let screen = scene
+            .make_camera()
+            .unwrap()
+            .render(scene.world.as_ref(), scene.make_renderer().as_ref());
+        assert!(screen.get(4, 4).min_element() > 0.0);
+    }
+}
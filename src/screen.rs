@@ -1,14 +1,56 @@
+use crate::tonemap::DisplayTransform;
 use crate::types::Color;
+use image::codecs::hdr::HdrEncoder;
+use image::{ImageError, ImageFormat, Rgb, Rgb32FImage, RgbImage};
 use std::io::Write;
 use std::path::Path;
-use image::{RgbImage, Rgb};
 
+// holds the linear radiance of every pixel, conversion to display values happens on export
 pub struct Screen {
     pub(crate) screen_data: Vec<Color>,
     width: usize,
     height: usize,
 }
 
+#[derive(Debug)]
+pub enum ScreenError {
+    Io(std::io::Error),
+    Image(ImageError),
+    UnsupportedFormat(String),
+}
+
+impl std::fmt::Display for ScreenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not write image: {e}"),
+            Self::Image(e) => write!(f, "could not encode image: {e}"),
+            Self::UnsupportedFormat(path) => write!(f, "unsupported image format for {path}"),
+        }
+    }
+}
+
+impl std::error::Error for ScreenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Image(e) => Some(e),
+            Self::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ScreenError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<ImageError> for ScreenError {
+    fn from(e: ImageError) -> Self {
+        Self::Image(e)
+    }
+}
+
 impl Screen {
     pub fn from(width: usize, height: usize) -> Self {
         Self {
@@ -18,6 +60,14 @@ impl Screen {
         }
     }
 
+    pub const fn width(&self) -> usize {
+        self.width
+    }
+
+    pub const fn height(&self) -> usize {
+        self.height
+    }
+
     pub fn get(&self, x: usize, y: usize) -> Color {
         self.screen_data[x + y * self.width]
     }
@@ -26,42 +76,132 @@ impl Screen {
         self.screen_data[x + y * self.width] = c;
     }
 
-    pub fn write_ppm(&self, path: &str) {
-        let file = std::fs::File::create(path).unwrap();
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ScreenError> {
+        self.save_with(path, &DisplayTransform::default())
+    }
+
+    // picks the format from the extension of the path, the display transform is only used for
+    // 8 bit formats
+    pub fn save_with(
+        &self,
+        path: impl AsRef<Path>,
+        transform: &DisplayTransform,
+    ) -> Result<(), ScreenError> {
+        let path = path.as_ref();
+
+        let extension = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .map(|x| x.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("ppm") => self.write_ppm(path, transform),
+            Some("png") => self.write_png(path, transform),
+            Some("exr") => self.write_exr(path),
+            Some("hdr") => self.write_hdr(path),
+            _ => match ImageFormat::from_path(path) {
+                Ok(format) if format.can_write() => {
+                    self.to_rgb_image(transform)
+                        .save_with_format(path, format)?;
+                    Ok(())
+                }
+                _ => Err(ScreenError::UnsupportedFormat(path.display().to_string())),
+            },
+        }
+    }
+
+    pub fn write_ppm(
+        &self,
+        path: impl AsRef<Path>,
+        transform: &DisplayTransform,
+    ) -> Result<(), ScreenError> {
+        let file = std::fs::File::create(path)?;
         let mut writer = std::io::BufWriter::new(file);
 
-        writeln!(writer, "P3").unwrap();
-        writeln!(writer, "{} {}", self.width, self.height).unwrap();
-        writeln!(writer, "255").unwrap();
+        writeln!(writer, "P3")?;
+        writeln!(writer, "{} {}", self.width, self.height)?;
+        writeln!(writer, "255")?;
 
         for j in 0..self.height {
             for i in 0..self.width {
-                let pixel_color = self.get(i, j);
-
-                writeln!(
-                    writer,
-                    "{} {} {}",
-                    pixel_color.x, pixel_color.y, pixel_color.z
-                )
-                .unwrap();
+                let [r, g, b] = transform.apply(self.get(i, j));
+
+                writeln!(writer, "{r} {g} {b}")?;
             }
         }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn write_png(
+        &self,
+        path: impl AsRef<Path>,
+        transform: &DisplayTransform,
+    ) -> Result<(), ScreenError> {
+        self.to_rgb_image(transform)
+            .save_with_format(path, ImageFormat::Png)?;
+        Ok(())
     }
-    
-    pub fn write_png(&self, path: &str) {
-        
+
+    pub fn to_rgb_image(&self, transform: &DisplayTransform) -> RgbImage {
         let mut img = RgbImage::new(self.width as u32, self.height as u32);
-        
+
+        for j in 0..self.height {
+            for i in 0..self.width {
+                img.put_pixel(i as u32, j as u32, Rgb(transform.apply(self.get(i, j))));
+            }
+        }
+
+        img
+    }
+
+    pub fn to_rgb32f_image(&self) -> Rgb32FImage {
+        let mut img = Rgb32FImage::new(self.width as u32, self.height as u32);
+
         for j in 0..self.height {
             for i in 0..self.width {
-                let pixel_color = self.get(i, j);
-                let r = pixel_color.x as u8;
-                let g = pixel_color.y as u8;
-                let b = pixel_color.z as u8;
-                img.put_pixel(i as u32, j as u32, Rgb([r, g, b]));
+                let pixel_color = self.get(i, j).as_vec3();
+                img.put_pixel(i as u32, j as u32, Rgb(pixel_color.to_array()));
             }
         }
 
-        img.save(Path::new(path)).unwrap()
+        img
+    }
+
+    pub fn write_exr(&self, path: impl AsRef<Path>) -> Result<(), ScreenError> {
+        self.to_rgb32f_image()
+            .save_with_format(path, ImageFormat::OpenExr)?;
+        Ok(())
+    }
+
+    // radiance rgbe
+    pub fn write_hdr(&self, path: impl AsRef<Path>) -> Result<(), ScreenError> {
+        let file = std::fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(file);
+
+        let pixels: Vec<Rgb<f32>> = self
+            .screen_data
+            .iter()
+            .map(|x| Rgb(x.as_vec3().max(glam::Vec3::ZERO).to_array()))
+            .collect();
+
+        HdrEncoder::new(&mut writer).encode(&pixels, self.width, self.height)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::screen::{Screen, ScreenError};
+
+    #[test]
+    fn check_unknown_extension_is_rejected() {
+        let screen = Screen::from(1, 1);
+        let result = screen.save("output.not_an_image");
+
+        assert!(matches!(result, Err(ScreenError::UnsupportedFormat(_))));
     }
 }
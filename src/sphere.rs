@@ -66,24 +66,8 @@ impl Sphere {
         (u, v)
     }
 
-    fn random_to_sphere(rad: f64, dist_sqrd: f64, prng: &mut PRNG<JsfLarge>) -> V3 {
-        let r1 = prng.gen_f64();
-        let r2 = prng.gen_f64();
-
-        let z = r2.mul_add((1.0 - rad * rad / dist_sqrd).sqrt() - 1.0, 1.0);
-        let phi = 2.0 * std::f64::consts::PI * r1;
-
-        let (phi_sin, phi_cos) = phi.sin_cos();
-        let z_sqrt = z.mul_add(-z, 1.0).max(0.0);
-        let x = phi_cos * z_sqrt;
-        let y = phi_sin * z_sqrt;
-
-        V3::new(x, y, z)
-    }
-}
-
-impl Hittable for Sphere {
-    fn hit(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+    // the nearest root of the ray in the interval
+    fn intersect(&self, r: &Ray, i: &Interval) -> Option<f64> {
         let current_center = self.center.at(r.time);
 
         let oc = current_center - r.origin;
@@ -110,6 +94,30 @@ impl Hittable for Sphere {
             }
         }
 
+        Some(root)
+    }
+
+    fn random_to_sphere(rad: f64, dist_sqrd: f64, prng: &mut PRNG<JsfLarge>) -> V3 {
+        let r1 = prng.gen_f64();
+        let r2 = prng.gen_f64();
+
+        let z = r2.mul_add((1.0 - rad * rad / dist_sqrd).sqrt() - 1.0, 1.0);
+        let phi = 2.0 * std::f64::consts::PI * r1;
+
+        let (phi_sin, phi_cos) = phi.sin_cos();
+        let z_sqrt = z.mul_add(-z, 1.0).max(0.0).sqrt();
+        let x = phi_cos * z_sqrt;
+        let y = phi_sin * z_sqrt;
+
+        V3::new(x, y, z)
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        let root = self.intersect(r, i)?;
+        let current_center = self.center.at(r.time);
+
         let p = r.at(root);
         let outward_normal = (p - current_center) / self.radius;
         let mut hr = HitRecord::from(p, outward_normal, root, 0.0, 0.0, self.mat.clone(), true);
@@ -128,6 +136,10 @@ impl Hittable for Sphere {
         self.aabb
     }
 
+    fn occluded(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> bool {
+        self.intersect(r, i).is_some()
+    }
+
     fn pdf_value(&self, origin: &P3, dir: &V3, time: f64, prng: &mut PRNG<JsfLarge>) -> f64 {
         // only works for stationary spheres!
 
@@ -52,7 +52,7 @@ impl Texture for CheckerTexture {
     }
 }
 
-struct NoiseTexture {
+pub struct NoiseTexture {
     noise: Perlin,
     scale: f64,
 }
@@ -69,7 +69,7 @@ impl Texture for NoiseTexture {
     }
 }
 
-struct ImageTexture {
+pub struct ImageTexture {
     data: Option<RgbaImage>,
 }
 
@@ -90,7 +90,7 @@ impl Texture for ImageTexture {
 
             let (i, j) = {
                 let mut i = (u * data.width() as f64) as u32;
-                let mut j = (v * data.width() as f64) as u32;
+                let mut j = (v * data.height() as f64) as u32;
 
                 if i >= data.width() {
                     i = data.width() - 1;
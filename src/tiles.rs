@@ -0,0 +1,224 @@
+// a rectangle of pixels, `x1` and `y1` are exclusive
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Tile {
+    pub const fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        Self { x0, y0, x1, y1 }
+    }
+
+    pub const fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub const fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub const fn pixel_count(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    pub fn intersect(&self, other: &Self) -> Self {
+        let x0 = self.x0.max(other.x0);
+        let y0 = self.y0.max(other.y0);
+        Self {
+            x0,
+            y0,
+            x1: self.x1.min(other.x1).max(x0),
+            y1: self.y1.min(other.y1).max(y0),
+        }
+    }
+}
+
+// the order the tiles are handed to the workers
+#[derive(Copy, Clone)]
+pub enum TileOrder {
+    Scanline,
+    Hilbert,
+    Spiral,
+}
+
+impl TileOrder {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "scanline" => Some(Self::Scanline),
+            "hilbert" => Some(Self::Hilbert),
+            "spiral" => Some(Self::Spiral),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct TileSettings {
+    pub tile_size: usize,
+    pub order: TileOrder,
+    // only the pixels inside are rendered, the rest of the image stays black
+    pub crop: Option<Tile>,
+}
+
+impl TileSettings {
+    pub const fn new() -> Self {
+        Self {
+            tile_size: 16,
+            order: TileOrder::Hilbert,
+            crop: None,
+        }
+    }
+
+    // splits the image, or the crop window of it, into tiles in the requested order
+    pub fn make_tiles(&self, width: usize, height: usize) -> Vec<Tile> {
+        let image = Tile::new(0, 0, width, height);
+        let area = self.crop.map_or(image, |x| x.intersect(&image));
+        let size = self.tile_size.max(1);
+
+        let columns = area.width().div_ceil(size);
+        let rows = area.height().div_ceil(size);
+
+        let mut grid: Vec<(usize, usize)> = (0..rows)
+            .flat_map(|y| (0..columns).map(move |x| (x, y)))
+            .collect();
+
+        match self.order {
+            TileOrder::Scanline => {}
+            TileOrder::Hilbert => {
+                let n = columns.max(rows).next_power_of_two();
+                grid.sort_by_key(|(x, y)| hilbert_index(n, *x, *y));
+            }
+            TileOrder::Spiral => {
+                // rings around the center, each walked by angle
+                let cx = (columns as f64 - 1.0) / 2.0;
+                let cy = (rows as f64 - 1.0) / 2.0;
+                let key = |(x, y): &(usize, usize)| {
+                    let dx = *x as f64 - cx;
+                    let dy = *y as f64 - cy;
+                    (dx.abs().max(dy.abs()), dy.atan2(dx))
+                };
+                grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+            }
+        }
+
+        grid.into_iter()
+            .map(|(x, y)| {
+                let x0 = area.x0 + x * size;
+                let y0 = area.y0 + y * size;
+                Tile::new(x0, y0, (x0 + size).min(area.x1), (y0 + size).min(area.y1))
+            })
+            .collect()
+    }
+}
+
+impl Default for TileSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// position of (x, y) along the hilbert curve filling an n by n grid, n a power of two
+fn hilbert_index(n: usize, x: usize, y: usize) -> usize {
+    let (mut x, mut y) = (x, y);
+    let mut d = 0;
+    let mut s = n / 2;
+
+    while s > 0 {
+        let rx = usize::from(x & s > 0);
+        let ry = usize::from(y & s > 0);
+        d += s * s * ((3 * rx) ^ ry);
+
+        // rotate the quadrant so the curve inside it starts at its origin
+        if ry == 0 {
+            if rx == 1 {
+                x = n - 1 - x;
+                y = n - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        s /= 2;
+    }
+
+    d
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::camera::{Camera, CameraConfig};
+    use crate::hittable_list::HittableList;
+    use crate::lambertian::Lambertian;
+    use crate::progress::CancelToken;
+    use crate::renderer::FullRenderer;
+    use crate::sphere::Sphere;
+    use crate::tiles::{Tile, TileOrder, TileSettings};
+    use crate::types::{Color, P3};
+    use std::sync::Arc;
+
+    #[test]
+    fn check_tiles_cover_the_crop_window_once() {
+        for order in [TileOrder::Scanline, TileOrder::Hilbert, TileOrder::Spiral] {
+            let settings = TileSettings {
+                tile_size: 7,
+                order,
+                crop: Some(Tile::new(5, 3, 45, 60)),
+            };
+
+            let mut covered = vec![0; 50 * 40];
+            for tile in settings.make_tiles(50, 40) {
+                for y in tile.y0..tile.y1 {
+                    for x in tile.x0..tile.x1 {
+                        covered[x + y * 50] += 1;
+                    }
+                }
+            }
+
+            for y in 0..40 {
+                for x in 0..50 {
+                    let inside = (5..45).contains(&x) && (3..40).contains(&y);
+                    assert_eq!(covered[x + y * 50], usize::from(inside));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_cropped_render_matches_full_render() {
+        let config = CameraConfig {
+            image_width: 24,
+            image_height: 16,
+            samples_per_pixel: 4,
+            fov: 40.0,
+            ..CameraConfig::default()
+        };
+        let camera = Camera::from_config(&config).unwrap();
+        let mut scene = HittableList::new();
+        scene.add(Box::new(Sphere::static_sphere(
+            P3::new(0.0, 0.0, -3.0),
+            0.5,
+            Arc::new(Lambertian::from_color(Color::splat(0.5))),
+        )));
+        let renderer = FullRenderer::new();
+
+        let full = camera.render(&scene, &renderer);
+        let settings = TileSettings {
+            tile_size: 5,
+            order: TileOrder::Spiral,
+            crop: Some(Tile::new(3, 2, 20, 11)),
+        };
+        let cropped =
+            camera.render_tiles(&scene, &renderer, &settings, &CancelToken::new(), |_| {});
+
+        for y in 0..16 {
+            for x in 0..24 {
+                let inside = (3..20).contains(&x) && (2..11).contains(&y);
+                let expected = if inside { full.get(x, y) } else { Color::ZERO };
+                assert_eq!(cropped.get(x, y), expected);
+            }
+        }
+    }
+}
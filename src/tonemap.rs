@@ -0,0 +1,161 @@
+use crate::types::Color;
+use crate::utility::linear_to_srgb;
+use glam::DMat3;
+
+// maps linear radiance into [0, 1] before display encoding
+#[derive(Copy, Clone)]
+pub enum Tonemapper {
+    Clamp,
+    Reinhard,
+    ExtendedReinhard { white: f64 },
+    AcesFilmic,
+    AgX,
+}
+
+impl Tonemapper {
+    pub fn apply(&self, c: Color) -> Color {
+        let c = c.max(Color::ZERO);
+
+        let mapped = match self {
+            Self::Clamp => c,
+            Self::Reinhard => c / (Color::ONE + c),
+            Self::ExtendedReinhard { white } => {
+                c * (Color::ONE + c / (white * white)) / (Color::ONE + c)
+            }
+            Self::AcesFilmic => Self::aces_filmic(c),
+            Self::AgX => Self::agx(c),
+        };
+
+        mapped.clamp(Color::ZERO, Color::ONE)
+    }
+
+    // Krzysztof Narkowicz's fit of the ACES reference rendering transform
+    fn aces_filmic(c: Color) -> Color {
+        let a = 2.51;
+        let b = 0.03;
+        let cc = 2.43;
+        let d = 0.59;
+        let e = 0.14;
+        (c * (a * c + b)) / (c * (cc * c + d) + e)
+    }
+
+    // minimal AgX base transform after Benjamin Wrensch, output linearized so it can go through the
+    // same display encoding as the other operators
+    fn agx(c: Color) -> Color {
+        let inset = DMat3::from_cols_array(&[
+            0.842479062253094,
+            0.0423282422610123,
+            0.0423756549057051,
+            0.0784335999999992,
+            0.878468636469772,
+            0.0784336,
+            0.0792237451477643,
+            0.0791661274605434,
+            0.879142973793104,
+        ]);
+        let outset = DMat3::from_cols_array(&[
+            1.19687900512017,
+            -0.0528968517574562,
+            -0.0529716355144438,
+            -0.0980208811401368,
+            1.15190312990417,
+            -0.0980434501171241,
+            -0.0990297440797205,
+            -0.0989611768448433,
+            1.15107367264116,
+        ]);
+
+        let min_ev = -12.47393;
+        let max_ev = 4.026069;
+
+        let log = (inset * c)
+            .max(Color::splat(1e-10))
+            .to_array()
+            .map(|x| (x.log2().clamp(min_ev, max_ev) - min_ev) / (max_ev - min_ev));
+
+        // sixth order fit of the default contrast curve
+        let coefficients = [15.5, -40.14, 31.96, -6.868, 0.4298, 0.1191, -0.00232];
+        let contrast = Color::from_array(log.map(|x| {
+            coefficients
+                .iter()
+                .fold(0.0f64, |acc, c| acc.mul_add(x, *c))
+        }));
+
+        (outset * contrast).max(Color::ZERO).powf(2.2)
+    }
+}
+
+// everything needed to turn the linear screen into 8 bit display values
+#[derive(Copy, Clone)]
+pub struct DisplayTransform {
+    pub tonemapper: Tonemapper,
+    pub exposure: f64, // in EV stops
+}
+
+impl DisplayTransform {
+    pub const fn new() -> Self {
+        Self {
+            tonemapper: Tonemapper::Clamp,
+            exposure: 0.0,
+        }
+    }
+
+    pub const fn from(tonemapper: Tonemapper, exposure: f64) -> Self {
+        Self {
+            tonemapper,
+            exposure,
+        }
+    }
+
+    pub fn apply(&self, c: Color) -> [u8; 3] {
+        let exposed = c * self.exposure.exp2();
+        let mapped = self.tonemapper.apply(exposed);
+        let encoded = Color::new(
+            linear_to_srgb(mapped.x),
+            linear_to_srgb(mapped.y),
+            linear_to_srgb(mapped.z),
+        );
+        let scaled = (255.99 * encoded)
+            .floor()
+            .clamp(Color::ZERO, Color::splat(255.0));
+        [scaled.x as u8, scaled.y as u8, scaled.z as u8]
+    }
+}
+
+impl Default for DisplayTransform {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::tonemap::{DisplayTransform, Tonemapper};
+    use crate::types::Color;
+
+    #[test]
+    fn check_tonemappers_stay_in_display_range() {
+        let tonemappers = [
+            Tonemapper::Clamp,
+            Tonemapper::Reinhard,
+            Tonemapper::ExtendedReinhard { white: 4.0 },
+            Tonemapper::AcesFilmic,
+            Tonemapper::AgX,
+        ];
+
+        for tonemapper in tonemappers {
+            let mut last = -1.0;
+            for x in [0.0, 0.01, 0.1, 0.5, 1.0, 4.0, 100.0, 1e6] {
+                let mapped = tonemapper.apply(Color::splat(x));
+                assert!(mapped.min_element() >= 0.0 && mapped.max_element() <= 1.0);
+                assert!(mapped.x >= last - 1e-9);
+                last = mapped.x;
+            }
+        }
+
+        let transform = DisplayTransform::default();
+        assert_eq!(transform.apply(Color::ZERO), [0, 0, 0]);
+        assert_eq!(transform.apply(Color::splat(10.0)), [255, 255, 255]);
+    }
+}
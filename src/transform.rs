@@ -0,0 +1,417 @@
+use crate::aabb::AABB;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::types::{P3, V3};
+use glam::{DAffine3, DMat3, DQuat};
+use smolprng::{JsfLarge, PRNG};
+use std::sync::Arc;
+
+// an affine matrix with what is needed to move rays in and hits out
+#[derive(Copy, Clone)]
+struct Affine {
+    matrix: DAffine3,
+    inverse: DAffine3,
+    // inverse transpose of the linear part, normals are carried back to world space with it
+    normal_matrix: DMat3,
+}
+
+impl Affine {
+    fn from(matrix: DAffine3) -> Self {
+        let inverse = matrix.inverse();
+        Self {
+            matrix,
+            inverse,
+            normal_matrix: inverse.matrix3.transpose(),
+        }
+    }
+
+    // the box around the transformed corners of the box
+    fn transform_aabb(&self, bbox: &AABB) -> AABB {
+        let corners = corners(bbox).map(|x| self.matrix.transform_point3(x));
+        let min = corners
+            .iter()
+            .fold(P3::splat(f64::MAX), |acc, x| acc.min(*x));
+        let max = corners
+            .iter()
+            .fold(P3::splat(f64::MIN), |acc, x| acc.max(*x));
+        AABB::from_points(min, max)
+    }
+
+    // move from world space to object space, the direction is not normalized so the ray keeps its
+    // parametrization and the hit distances stay the same
+    fn object_ray(&self, r: &Ray) -> Ray {
+        Ray {
+            origin: self.inverse.transform_point3(r.origin),
+            direction: self.inverse.transform_vector3(r.direction),
+            time: r.time,
+        }
+    }
+
+    fn hit(
+        &self,
+        object: &dyn Hittable,
+        r: &Ray,
+        i: &Interval,
+        prng: &mut PRNG<JsfLarge>,
+    ) -> Option<HitRecord> {
+        let mut rec = object.hit(&self.object_ray(r), i, prng)?;
+
+        // the inverse transpose keeps the normal facing the same side of the ray
+        rec.p = self.matrix.transform_point3(rec.p);
+        rec.normal = (self.normal_matrix * rec.normal).normalize();
+        Some(rec)
+    }
+
+    // the object's density is over the solid angle in object space, a linear map A stretches the
+    // solid angle around the unit direction w by |det A| / |A w|^3
+    fn pdf_value(
+        &self,
+        object: &dyn Hittable,
+        origin: &P3,
+        dir: &V3,
+        time: f64,
+        prng: &mut PRNG<JsfLarge>,
+    ) -> f64 {
+        let object_dir = self.inverse.transform_vector3(*dir).normalize();
+        let object_origin = self.inverse.transform_point3(*origin);
+        let pdf = object.pdf_value(&object_origin, &object_dir, time, prng);
+
+        let stretch = self.matrix.transform_vector3(object_dir).length();
+        pdf * stretch.powi(3) / self.matrix.matrix3.determinant().abs()
+    }
+
+    fn random(
+        &self,
+        object: &dyn Hittable,
+        origin: &P3,
+        time: f64,
+        prng: &mut PRNG<JsfLarge>,
+    ) -> V3 {
+        let object_origin = self.inverse.transform_point3(*origin);
+        let dir = object.random(&object_origin, time, prng);
+        self.matrix.transform_vector3(dir)
+    }
+}
+
+fn corners(bbox: &AABB) -> [P3; 8] {
+    std::array::from_fn(|i| {
+        P3::new(
+            if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+            if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+            if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+        )
+    })
+}
+
+// places an object with an affine matrix, e.g. a rotation about any axis, a scale or a shear, the
+// rays are moved into object space so the object itself is not changed
+pub struct Transform {
+    object: Arc<dyn Hittable>,
+    affine: Affine,
+    aabb: AABB,
+}
+
+impl Transform {
+    pub fn from(object: Arc<dyn Hittable>, matrix: DAffine3) -> Self {
+        let affine = Affine::from(matrix);
+        let aabb = affine.transform_aabb(&object.bounding_box());
+        Self {
+            object,
+            affine,
+            aabb,
+        }
+    }
+
+    pub const fn matrix(&self) -> DAffine3 {
+        self.affine.matrix
+    }
+}
+
+impl Hittable for Transform {
+    fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        self.affine.hit(self.object.as_ref(), r, i, prng)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        self.object.occluded(&self.affine.object_ray(r), i, prng)
+    }
+
+    fn pdf_value(&self, origin: &P3, dir: &V3, time: f64, prng: &mut PRNG<JsfLarge>) -> f64 {
+        self.affine
+            .pdf_value(self.object.as_ref(), origin, dir, time, prng)
+    }
+
+    fn random(&self, origin: &P3, time: f64, prng: &mut PRNG<JsfLarge>) -> V3 {
+        self.affine.random(self.object.as_ref(), origin, time, prng)
+    }
+}
+
+// the pose of an animated object at a point in time, scaled, then rotated, then translated
+#[derive(Copy, Clone)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: V3,
+    pub rotation: DQuat,
+    pub scale: V3,
+}
+
+impl Keyframe {
+    pub const fn new(time: f64) -> Self {
+        Self {
+            time,
+            translation: V3::ZERO,
+            rotation: DQuat::IDENTITY,
+            scale: V3::ONE,
+        }
+    }
+
+    pub fn matrix(&self) -> DAffine3 {
+        DAffine3::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+}
+
+// steps every keyframe interval is sampled at to bound the motion
+const MOTION_BOUND_STEPS: usize = 32;
+
+// an object moving between keyframes, translation and scale are interpolated linearly and the
+// rotation with slerp (so along the shorter arc), before the first and after the last keyframe
+// the object holds still
+pub struct AnimatedTransform {
+    object: Arc<dyn Hittable>,
+    keyframes: Vec<Keyframe>,
+    aabb: AABB,
+}
+
+impl AnimatedTransform {
+    pub fn from(object: Arc<dyn Hittable>, keyframes: Vec<Keyframe>) -> Self {
+        assert!(!keyframes.is_empty());
+
+        let mut keyframes = keyframes;
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        // the corners of the object's box are followed through every interval, the path between
+        // two samples strays from them by less than the step, so the box is padded by the
+        // largest step
+        let object_corners = corners(&object.bounding_box());
+        let mut min = P3::splat(f64::MAX);
+        let mut max = P3::splat(f64::MIN);
+        let mut padding: f64 = 0.0;
+
+        let mut times = vec![keyframes[0].time];
+        for pair in keyframes.windows(2) {
+            let (start, end) = (pair[0].time, pair[1].time);
+            times.extend(
+                (1..=MOTION_BOUND_STEPS)
+                    .map(|x| (end - start).mul_add(x as f64 / MOTION_BOUND_STEPS as f64, start)),
+            );
+        }
+
+        let mut previous: Option<[P3; 8]> = None;
+        for time in times {
+            let matrix = Self::interpolate(&keyframes, time).matrix();
+            let points = object_corners.map(|x| matrix.transform_point3(x));
+
+            for (k, p) in points.iter().enumerate() {
+                min = min.min(*p);
+                max = max.max(*p);
+                if let Some(last) = previous {
+                    padding = padding.max(p.distance(last[k]));
+                }
+            }
+            previous = Some(points);
+        }
+
+        let aabb = AABB::from_points(min - padding, max + padding);
+
+        Self {
+            object,
+            keyframes,
+            aabb,
+        }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    fn interpolate(keyframes: &[Keyframe], time: f64) -> Keyframe {
+        let next = keyframes.partition_point(|x| x.time <= time);
+
+        if next == 0 {
+            return keyframes[0];
+        }
+        if next == keyframes.len() {
+            return keyframes[next - 1];
+        }
+
+        let (a, b) = (&keyframes[next - 1], &keyframes[next]);
+        let s = (time - a.time) / (b.time - a.time);
+
+        Keyframe {
+            time,
+            translation: a.translation.lerp(b.translation, s),
+            rotation: a.rotation.slerp(b.rotation, s),
+            scale: a.scale.lerp(b.scale, s),
+        }
+    }
+
+    fn affine_at(&self, time: f64) -> Affine {
+        Affine::from(Self::interpolate(&self.keyframes, time).matrix())
+    }
+}
+
+impl Hittable for AnimatedTransform {
+    fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        self.affine_at(r.time).hit(self.object.as_ref(), r, i, prng)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        let object_ray = self.affine_at(r.time).object_ray(r);
+        self.object.occluded(&object_ray, i, prng)
+    }
+
+    fn pdf_value(&self, origin: &P3, dir: &V3, time: f64, prng: &mut PRNG<JsfLarge>) -> f64 {
+        self.affine_at(time)
+            .pdf_value(self.object.as_ref(), origin, dir, time, prng)
+    }
+
+    fn random(&self, origin: &P3, time: f64, prng: &mut PRNG<JsfLarge>) -> V3 {
+        self.affine_at(time)
+            .random(self.object.as_ref(), origin, time, prng)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::hittable::Hittable;
+    use crate::interval::Interval;
+    use crate::lambertian::Lambertian;
+    use crate::quad::Quad;
+    use crate::ray::Ray;
+    use crate::transform::{AnimatedTransform, Keyframe, Transform};
+    use crate::types::{Color, P3, V3};
+    use crate::utility::{make_prng_from, sample_unit_vector};
+    use glam::{DAffine3, DQuat};
+    use std::sync::Arc;
+
+    #[test]
+    fn check_transformed_quad_matches_moved_quad() {
+        let mat = Arc::new(Lambertian::from_color(Color::ONE));
+        let (q, u, v) = (P3::new(-1.0, -1.0, 0.0), V3::X * 2.0, V3::Y * 2.0);
+
+        // a rotation about a tilted axis, a non uniform scale and a shear
+        let matrix = DAffine3::from_translation(V3::new(1.0, 2.0, -3.0))
+            * DAffine3::from_quat(DQuat::from_axis_angle(
+                V3::new(1.0, 1.0, 0.0).normalize(),
+                0.7,
+            ))
+            * DAffine3::from_cols_array(&[
+                2.0, 0.0, 0.0, 0.5, 0.7, 0.0, 0.0, 0.0, 1.5, 0.0, 0.0, 0.0,
+            ]);
+
+        let transformed = Transform::from(Arc::new(Quad::new(q, u, v, mat.clone())), matrix);
+        let moved = Quad::new(
+            matrix.transform_point3(q),
+            matrix.transform_vector3(u),
+            matrix.transform_vector3(v),
+            mat,
+        );
+
+        let bbox = transformed.bounding_box();
+        assert!(bbox.min.cmple(moved.bounding_box().min + 1e-9).all());
+        assert!(bbox.max.cmpge(moved.bounding_box().max - 1e-9).all());
+
+        let mut prng = make_prng_from(11);
+        let origin = P3::new(0.5, 1.0, 4.0);
+        let mut hits = 0;
+
+        for _ in 0..2000 {
+            let dir = sample_unit_vector(&mut prng);
+            let ray = Ray::from(&origin, &dir, 0.0);
+            let a = transformed.hit(&ray, &Interval::casting_default(), &mut prng);
+            let b = moved.hit(&ray, &Interval::casting_default(), &mut prng);
+
+            match (a, b) {
+                (None, None) => {}
+                (Some(a), Some(b)) => {
+                    hits += 1;
+                    assert!((a.t - b.t).abs() < 1e-9);
+                    assert!((a.p - b.p).length() < 1e-9);
+                    assert!((a.normal - b.normal).length() < 1e-9);
+                    assert_eq!(a.is_front_face, b.is_front_face);
+                }
+                _ => panic!("transformed and moved quad disagree"),
+            }
+
+            let a = transformed.pdf_value(&origin, &dir, 0.0, &mut prng);
+            let b = moved.pdf_value(&origin, &dir, 0.0, &mut prng);
+            assert!((a - b).abs() <= 1e-9 * b.max(1.0));
+
+            // light samples of the transformed quad land on the moved one
+            let sample = transformed.random(&origin, 0.0, &mut prng);
+            assert!(moved.pdf_value(&origin, &sample, 0.0, &mut prng) > 0.0);
+        }
+
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn check_animated_transform_follows_keyframes() {
+        let mat = Arc::new(Lambertian::from_color(Color::ONE));
+        let quad = Arc::new(Quad::new(
+            P3::new(-1.0, -1.0, 0.0),
+            V3::X * 2.0,
+            V3::Y * 2.0,
+            mat,
+        ));
+
+        let keyframes = vec![
+            Keyframe {
+                translation: V3::new(3.0, 0.0, 0.0),
+                rotation: DQuat::from_rotation_y(1.5),
+                scale: V3::splat(2.0),
+                ..Keyframe::new(1.0)
+            },
+            Keyframe::new(0.0),
+        ];
+        let animated = AnimatedTransform::from(quad.clone(), keyframes);
+        let bbox = animated.bounding_box();
+
+        let mut prng = make_prng_from(4);
+        for k in 0..=20 {
+            let time = k as f64 / 20.0;
+            let matrix = DAffine3::from_scale_rotation_translation(
+                V3::ONE.lerp(V3::splat(2.0), time),
+                DQuat::IDENTITY.slerp(DQuat::from_rotation_y(1.5), time),
+                V3::new(3.0 * time, 0.0, 0.0),
+            );
+            let still = Transform::from(quad.clone(), matrix);
+
+            assert!(bbox.min.cmple(still.bounding_box().min).all());
+            assert!(bbox.max.cmpge(still.bounding_box().max).all());
+
+            for _ in 0..100 {
+                let origin = P3::new(1.0, 0.5, 6.0);
+                let ray = Ray::from(&origin, &sample_unit_vector(&mut prng), time);
+                let a = animated.hit(&ray, &Interval::casting_default(), &mut prng);
+                let b = still.hit(&ray, &Interval::casting_default(), &mut prng);
+                match (a, b) {
+                    (None, None) => {}
+                    (Some(a), Some(b)) => assert!((a.t - b.t).abs() < 1e-9),
+                    _ => panic!("animated and still transform disagree"),
+                }
+            }
+        }
+    }
+}
@@ -7,7 +7,7 @@ use crate::types::V3;
 use smolprng::{JsfLarge, PRNG};
 use std::sync::Arc;
 
-struct Translate {
+pub struct Translate {
     object: Arc<dyn Hittable>,
     offset: V3,
     aabb: AABB,
@@ -24,14 +24,20 @@ impl Translate {
     }
 }
 
-impl Hittable for Translate {
-    fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
-        // move the ray for the offset
-        let offset_ray = Ray {
+impl Translate {
+    // move the ray for the offset
+    fn offset_ray(&self, r: &Ray) -> Ray {
+        Ray {
             origin: r.origin - self.offset,
             direction: r.direction,
             time: r.time,
-        };
+        }
+    }
+}
+
+impl Hittable for Translate {
+    fn hit(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        let offset_ray = self.offset_ray(r);
 
         // do we hit?
         let hr = self.object.hit(&offset_ray, i, prng);
@@ -49,4 +55,8 @@ impl Hittable for Translate {
     fn bounding_box(&self) -> AABB {
         self.aabb
     }
+
+    fn occluded(&self, r: &Ray, i: &Interval, prng: &mut PRNG<JsfLarge>) -> bool {
+        self.object.occluded(&self.offset_ray(r), i, prng)
+    }
 }
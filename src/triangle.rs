@@ -0,0 +1,222 @@
+use crate::aabb::AABB;
+use crate::hit_record::HitRecord;
+use crate::hittable::Hittable;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::types::{P3, V3};
+use glam::DVec2;
+use smolprng::{JsfLarge, PRNG};
+use std::sync::Arc;
+
+pub struct Triangle {
+    vertices: [P3; 3],
+    normals: [V3; 3],
+    uvs: [DVec2; 3],
+    mat: Arc<dyn Material>,
+    aabb: AABB,
+    normal: V3,
+    area: f64,
+}
+
+impl Triangle {
+    // flat shaded, with the uvs set to the barycentric coordinates
+    pub fn new(a: P3, b: P3, c: P3, mat: Arc<dyn Material>) -> Self {
+        let normal = (b - a).cross(c - a).normalize();
+        Self::from(
+            [a, b, c],
+            [normal; 3],
+            [DVec2::ZERO, DVec2::X, DVec2::Y],
+            mat,
+        )
+    }
+
+    pub fn from(
+        vertices: [P3; 3],
+        normals: [V3; 3],
+        uvs: [DVec2; 3],
+        mat: Arc<dyn Material>,
+    ) -> Self {
+        let [a, b, c] = vertices;
+        let n = (b - a).cross(c - a);
+        let area = 0.5 * n.length();
+        let normal = n.normalize();
+        let aabb = AABB::from_points(a.min(b).min(c), a.max(b).max(c));
+
+        Self {
+            vertices,
+            normals,
+            uvs,
+            mat,
+            aabb,
+            normal,
+            area,
+        }
+    }
+}
+
+// Möller–Trumbore, returns the ray parameter and the barycentric coordinates of the second and
+// third vertex
+pub fn intersect_triangle(r: &Ray, vertices: &[P3; 3], i: &Interval) -> Option<(f64, f64, f64)> {
+    let edge_1 = vertices[1] - vertices[0];
+    let edge_2 = vertices[2] - vertices[0];
+
+    let p_vec = r.direction.cross(edge_2);
+    let det = edge_1.dot(p_vec);
+
+    // if we are parallel, we never hit
+    if det.abs() <= 1E-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+
+    let t_vec = r.origin - vertices[0];
+    let b1 = t_vec.dot(p_vec) * inv_det;
+
+    if !(0.0..=1.0).contains(&b1) {
+        return None;
+    }
+
+    let q_vec = t_vec.cross(edge_1);
+    let b2 = r.direction.dot(q_vec) * inv_det;
+
+    if b2 < 0.0 || b1 + b2 > 1.0 {
+        return None;
+    }
+
+    let t = edge_2.dot(q_vec) * inv_det;
+
+    if !i.surrounds(t) {
+        return None;
+    }
+
+    Some((t, b1, b2))
+}
+
+// builds the hit record from the barycentric coordinates, the interpolated normal is used for
+// shading while the geometric normal decides which face was hit
+#[allow(clippy::too_many_arguments)]
+pub fn shade_triangle(
+    r: &Ray,
+    t: f64,
+    b1: f64,
+    b2: f64,
+    geometric_normal: &V3,
+    normals: &[V3; 3],
+    uvs: &[DVec2; 3],
+    mat: &Arc<dyn Material>,
+) -> HitRecord {
+    let b0 = 1.0 - b1 - b2;
+
+    let mut shading_normal = b0 * normals[0] + b1 * normals[1] + b2 * normals[2];
+    shading_normal = shading_normal.try_normalize().unwrap_or(*geometric_normal);
+
+    if shading_normal.dot(*geometric_normal) < 0.0 {
+        shading_normal = -shading_normal;
+    }
+
+    let uv = b0 * uvs[0] + b1 * uvs[1] + b2 * uvs[2];
+
+    let mut hr = HitRecord::from(r.at(t), *geometric_normal, t, uv.x, uv.y, mat.clone(), true);
+    hr.set_face_normal(r, geometric_normal);
+
+    hr.normal = if hr.is_front_face {
+        shading_normal
+    } else {
+        -shading_normal
+    };
+
+    hr
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> Option<HitRecord> {
+        let (t, b1, b2) = intersect_triangle(r, &self.vertices, i)?;
+
+        Some(shade_triangle(
+            r,
+            t,
+            b1,
+            b2,
+            &self.normal,
+            &self.normals,
+            &self.uvs,
+            &self.mat,
+        ))
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.aabb
+    }
+
+    fn occluded(&self, r: &Ray, i: &Interval, _prng: &mut PRNG<JsfLarge>) -> bool {
+        intersect_triangle(r, &self.vertices, i).is_some()
+    }
+
+    fn pdf_value(&self, origin: &P3, dir: &V3, time: f64, _prng: &mut PRNG<JsfLarge>) -> f64 {
+        let r = Ray::from(origin, dir, time);
+
+        match intersect_triangle(&r, &self.vertices, &Interval::casting_default()) {
+            None => 0.0,
+            Some((t, _, _)) => {
+                let dist_squared = t * t * dir.length_squared();
+                let cosine = (self.normal.dot(*dir) / dir.length()).abs();
+
+                dist_squared / (cosine * self.area)
+            }
+        }
+    }
+
+    fn random(&self, origin: &P3, _time: f64, prng: &mut PRNG<JsfLarge>) -> V3 {
+        sample_triangle(&self.vertices, prng) - origin
+    }
+}
+
+// uniformly distributed point on the triangle
+pub fn sample_triangle(vertices: &[P3; 3], prng: &mut PRNG<JsfLarge>) -> P3 {
+    let r1_sqrt = prng.gen_f64().sqrt();
+    let r2 = prng.gen_f64();
+
+    let b0 = 1.0 - r1_sqrt;
+    let b1 = r1_sqrt * (1.0 - r2);
+    let b2 = r1_sqrt * r2;
+
+    b0 * vertices[0] + b1 * vertices[1] + b2 * vertices[2]
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::hittable::Hittable;
+    use crate::interval::Interval;
+    use crate::lambertian::Lambertian;
+    use crate::ray::Ray;
+    use crate::triangle::Triangle;
+    use crate::types::{Color, P3, V3};
+    use crate::utility::make_prng_default;
+    use std::sync::Arc;
+
+    #[test]
+    fn check_ray_hits_triangle_barycentrics() {
+        let mat = Arc::new(Lambertian::from_color(Color::ONE));
+        let tri = Triangle::new(P3::ZERO, V3::X, V3::Y, mat);
+        let mut prng = make_prng_default();
+
+        let ray = Ray::from(&P3::new(0.25, 0.5, 2.0), &-V3::Z, 0.0);
+        let rec = tri
+            .hit(&ray, &Interval::casting_default(), &mut prng)
+            .unwrap();
+
+        assert!((rec.t - 2.0).abs() < 1e-12);
+        assert!((rec.u - 0.25).abs() < 1e-12);
+        assert!((rec.v - 0.5).abs() < 1e-12);
+        assert!(rec.is_front_face);
+
+        let miss = Ray::from(&P3::new(0.75, 0.5, 2.0), &-V3::Z, 0.0);
+        assert!(
+            tri.hit(&miss, &Interval::casting_default(), &mut prng)
+                .is_none()
+        );
+    }
+}
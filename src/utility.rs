@@ -14,6 +14,19 @@ pub fn make_prng_from(seed: u64) -> PRNG<JsfLarge> {
     }
 }
 
+// splitmix64 finalizer, small changes of the input change about half the bits of the output
+pub const fn mix_seed(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+// folds the values into the seed one at a time, so (1, 3) and (3, 1) give unrelated seeds
+pub fn derive_seed(seed: u64, values: &[u64]) -> u64 {
+    values.iter().fold(mix_seed(seed), |h, x| mix_seed(h ^ *x))
+}
+
 pub fn random_double(prng: &mut PRNG<JsfLarge>) -> f64 {
     prng.gen_f64()
 }
@@ -93,6 +106,17 @@ pub fn linear_to_gamma(x: f64) -> f64 {
     if x > 0.0 { x.sqrt() } else { 0.0 }
 }
 
+// the srgb opto-electronic transfer function
+pub fn linear_to_srgb(x: f64) -> f64 {
+    if x <= 0.0 {
+        0.0
+    } else if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055f64.mul_add(x.powf(1.0 / 2.4), -0.055)
+    }
+}
+
 pub fn reflect(v_in: &V3, normal: &V3) -> V3 {
     v_in - 2.0 * v_in.dot(*normal) * normal
 }